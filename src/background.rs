@@ -0,0 +1,316 @@
+//! Background layer drawn behind the particles
+
+use crate::config::{get, get_vec3};
+use crate::constants::*;
+use crate::postprocess::{Pass, Quad};
+use crate::shaders::BACKGROUND_SHADER_SOURCE;
+use crate::texture::{TextureOptions, TextureSource};
+use wasm_bindgen::prelude::*;
+use web_sys::{WebGlRenderingContext as GL, WebGlTexture};
+
+/// How a background image is scaled to the canvas.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BackgroundFit {
+    /// Fills the canvas, cropping the image.
+    #[default]
+    Cover = 0,
+    /// Shows the whole image over the background colors.
+    Contain = 1,
+}
+
+impl BackgroundFit {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "cover" => Some(Self::Cover),
+            "contain" => Some(Self::Contain),
+            _ => None,
+        }
+    }
+}
+
+/// Colors and image filling the canvas before the particles are drawn.
+/// The default is transparent, leaving the background to the page.
+#[derive(Clone, Debug, Default)]
+pub struct Background {
+    /// Top and bottom colors, equal for a solid color.
+    pub gradient: Option<([f32; 3], [f32; 3])>,
+    pub image: Option<TextureSource>,
+    pub fit: BackgroundFit,
+}
+
+impl Background {
+    pub fn solid(color: [f32; 3]) -> Self {
+        Self {
+            gradient: Some((color, color)),
+            ..Self::default()
+        }
+    }
+
+    /// Accepts `"transparent"`, an `[r, g, b]` color, a texture source or
+    /// `{ color, top, bottom, image, fit }`.
+    pub fn from_js(value: &JsValue) -> Self {
+        if js_sys::Array::is_array(value) {
+            let rgb: Vec<f32> = js_sys::Array::from(value)
+                .iter()
+                .filter_map(|x| x.as_f64().map(|n| n as f32))
+                .collect();
+            return match rgb.as_slice() {
+                [r, g, b, ..] => Self::solid([*r, *g, *b]),
+                _ => Self::default(),
+            };
+        }
+        if let Some(image) = TextureSource::from_js(value) {
+            return Self {
+                image: Some(image),
+                ..Self::default()
+            };
+        }
+        if !value.is_object() {
+            return Self::default();
+        }
+
+        let mut background = Self::default();
+        let color = get_vec3(value, "color");
+        background.gradient = gradient(
+            get_vec3(value, "top").or(color),
+            get_vec3(value, "bottom").or(color),
+        );
+        background.image = get(value, "image").and_then(|v| TextureSource::from_js(&v));
+        if let Some(fit) = get(value, "fit")
+            .and_then(|v| v.as_string())
+            .and_then(|s| BackgroundFit::parse(&s))
+        {
+            background.fit = fit;
+        }
+        background
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.gradient.is_some() || self.image.is_some()
+    }
+
+    /// Canvas size in units of the displayed image size, so that
+    /// `(uv - 0.5) * scale + 0.5` maps canvas to image coordinates.
+    pub fn image_scale(&self, image: (u32, u32), canvas: (u32, u32)) -> [f32; 2] {
+        let (iw, ih) = (image.0.max(1) as f32, image.1.max(1) as f32);
+        let (cw, ch) = (canvas.0.max(1) as f32, canvas.1.max(1) as f32);
+        let (sx, sy) = (cw / iw, ch / ih);
+        let scale = match self.fit {
+            BackgroundFit::Cover => sx.max(sy),
+            BackgroundFit::Contain => sx.min(sy),
+        };
+        [sx / scale, sy / scale]
+    }
+}
+
+/// Gradient between the given stops. A single stop fills the whole canvas.
+fn gradient(top: Option<[f32; 3]>, bottom: Option<[f32; 3]>) -> Option<([f32; 3], [f32; 3])> {
+    match (top, bottom) {
+        (Some(top), Some(bottom)) => Some((top, bottom)),
+        (Some(c), None) | (None, Some(c)) => Some((c, c)),
+        (None, None) => None,
+    }
+}
+
+/// Draws a `Background` with a fullscreen quad.
+pub struct BackgroundLayer {
+    quad: Quad,
+    pass: Pass,
+    texture: Option<WebGlTexture>,
+    /// Size of the uploaded image before resizing, used for fitting.
+    image_size: (u32, u32),
+    /// Image waiting to finish loading.
+    pending: Option<TextureSource>,
+}
+
+impl BackgroundLayer {
+    pub fn new(gl: &GL) -> Result<Self, JsValue> {
+        Ok(Self {
+            quad: Quad::new(gl)?,
+            pass: Pass::new(
+                gl,
+                BACKGROUND_SHADER_SOURCE,
+                &["u_top", "u_bottom", "u_image", "u_hasImage", "u_imageScale"],
+            )?,
+            texture: None,
+            image_size: (0, 0),
+            pending: None,
+        })
+    }
+
+    /// Replaces the image, deferring the upload while it is still loading.
+    pub fn set_image(&mut self, gl: &GL, image: Option<&TextureSource>) -> Result<(), JsValue> {
+        if let Some(texture) = self.texture.take() {
+            gl.delete_texture(Some(&texture));
+        }
+        self.pending = None;
+        match image {
+            Some(image) if image.is_ready() => self.upload(gl, image),
+            Some(image) => {
+                self.pending = Some(image.clone());
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Uploads the pending image once it has loaded.
+    pub fn poll(&mut self, gl: &GL) -> Result<(), JsValue> {
+        match self.pending.take() {
+            Some(image) if image.is_ready() => self.upload(gl, &image),
+            pending => {
+                self.pending = pending;
+                Ok(())
+            }
+        }
+    }
+
+    fn upload(&mut self, gl: &GL, image: &TextureSource) -> Result<(), JsValue> {
+        let (width, height) = image.size();
+        let options = TextureOptions {
+            mipmaps: false,
+            size: None,
+            ..TextureOptions::default()
+        };
+        let (upload_width, upload_height) = options.upload_size(width, height);
+        let resized = if (upload_width, upload_height) != (width, height) {
+            Some(image.resized(upload_width, upload_height)?)
+        } else {
+            None
+        };
+
+        let texture = gl.create_texture().ok_or("Failed to create texture")?;
+        gl.active_texture(GL::TEXTURE0 + BACKGROUND_TEXTURE_UNIT);
+        gl.bind_texture(GL::TEXTURE_2D, Some(&texture));
+        gl.pixel_storei(GL::UNPACK_FLIP_Y_WEBGL, 0);
+        gl.pixel_storei(GL::UNPACK_PREMULTIPLY_ALPHA_WEBGL, 0);
+        let uploaded = resized.as_ref().unwrap_or(image).upload(gl);
+        for (param, value) in [
+            (GL::TEXTURE_MIN_FILTER, GL::LINEAR),
+            (GL::TEXTURE_MAG_FILTER, GL::LINEAR),
+            (GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE),
+            (GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE),
+        ] {
+            gl.tex_parameteri(GL::TEXTURE_2D, param, value as i32);
+        }
+        gl.active_texture(GL::TEXTURE0);
+        if let Err(err) = uploaded {
+            gl.delete_texture(Some(&texture));
+            return Err(err);
+        }
+
+        self.texture = Some(texture);
+        self.image_size = (width, height);
+        Ok(())
+    }
+
+    /// Fills the current framebuffer. Leaves blending on and texture unit 0
+    /// active, but not the particle program.
+    pub fn draw(&self, gl: &GL, background: &Background, width: u32, height: u32) {
+        let pass = &self.pass;
+        gl.disable(GL::BLEND);
+        self.quad.bind(gl);
+        gl.use_program(Some(&pass.program));
+
+        let transparent = [0.0; 3];
+        let (top, bottom, alpha) = match background.gradient {
+            Some((top, bottom)) => (top, bottom, 1.0),
+            None => (transparent, transparent, 0.0),
+        };
+        gl.uniform4f(pass.uniform("u_top"), top[0], top[1], top[2], alpha);
+        gl.uniform4f(
+            pass.uniform("u_bottom"),
+            bottom[0],
+            bottom[1],
+            bottom[2],
+            alpha,
+        );
+
+        let has_image = self.texture.is_some();
+        gl.uniform1f(pass.uniform("u_hasImage"), has_image as i32 as f32);
+        if let Some(texture) = &self.texture {
+            gl.active_texture(GL::TEXTURE0 + BACKGROUND_TEXTURE_UNIT);
+            gl.bind_texture(GL::TEXTURE_2D, Some(texture));
+            gl.uniform1i(pass.uniform("u_image"), BACKGROUND_TEXTURE_UNIT as i32);
+            let [sx, sy] = background.image_scale(self.image_size, (width, height));
+            gl.uniform2f(pass.uniform("u_imageScale"), sx, sy);
+        }
+        Pass::draw(gl);
+
+        self.quad.unbind(gl);
+        gl.active_texture(GL::TEXTURE0);
+        gl.enable(GL::BLEND);
+    }
+
+    pub fn delete(&self, gl: &GL) {
+        if let Some(texture) = &self.texture {
+            gl.delete_texture(Some(texture));
+        }
+        self.quad.delete(gl);
+        gl.delete_program(Some(&self.pass.program));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scale(fit: BackgroundFit, image: (u32, u32), canvas: (u32, u32)) -> [f32; 2] {
+        Background {
+            fit,
+            ..Background::default()
+        }
+        .image_scale(image, canvas)
+    }
+
+    #[test]
+    fn cover_crops_the_overhanging_side() {
+        // Wider image: canvas shows the middle half of its width
+        assert_eq!(
+            scale(BackgroundFit::Cover, (200, 100), (100, 100)),
+            [0.5, 1.0]
+        );
+        // Taller image: canvas shows a quarter of its height
+        assert_eq!(
+            scale(BackgroundFit::Cover, (100, 400), (200, 200)),
+            [1.0, 0.25]
+        );
+    }
+
+    #[test]
+    fn contain_leaves_bars_on_the_short_side() {
+        assert_eq!(
+            scale(BackgroundFit::Contain, (200, 100), (100, 100)),
+            [1.0, 2.0]
+        );
+        assert_eq!(
+            scale(BackgroundFit::Contain, (100, 400), (200, 200)),
+            [4.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn matching_aspect_fills_exactly() {
+        for fit in [BackgroundFit::Cover, BackgroundFit::Contain] {
+            assert_eq!(scale(fit, (160, 90), (1920, 1080)), [1.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn empty_sizes_stay_finite() {
+        for fit in [BackgroundFit::Cover, BackgroundFit::Contain] {
+            let [x, y] = scale(fit, (0, 0), (0, 0));
+            assert!(x.is_finite() && y.is_finite());
+        }
+    }
+
+    #[test]
+    fn single_stop_fills_the_canvas() {
+        let (top, bottom) = ([1.0, 0.0, 0.0], [0.0, 0.0, 1.0]);
+        assert_eq!(gradient(Some(top), Some(bottom)), Some((top, bottom)));
+        assert_eq!(gradient(Some(top), None), Some((top, top)));
+        assert_eq!(gradient(None, Some(bottom)), Some((bottom, bottom)));
+        assert_eq!(gradient(None, None), None);
+    }
+}
@@ -0,0 +1,528 @@
+//! Camera and matrix math
+
+use crate::config::{get, get_bool, get_f32, get_vec3};
+use crate::constants::*;
+use wasm_bindgen::prelude::*;
+
+/// Column-major 4x4 matrix, as expected by `uniformMatrix4fv`.
+pub type Mat4 = [f32; 16];
+
+/// How the camera orbits around its target.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrbitMode {
+    Off,
+    /// Slow continuous rotation around the target.
+    Auto,
+    /// Pitch follows the document scroll position.
+    Scroll,
+    /// Follows values passed to `set_device_orientation`.
+    Orientation,
+}
+
+impl OrbitMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "off" | "none" => Some(Self::Off),
+            "auto" => Some(Self::Auto),
+            "scroll" => Some(Self::Scroll),
+            "orientation" => Some(Self::Orientation),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CameraConfig {
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub position: [f32; 3],
+    /// `None` looks halfway into the flakes in front of the camera, which
+    /// fill `z` in `[-depth, depth]`.
+    pub target: Option<[f32; 3]>,
+    pub roll: f32,
+    pub orthographic: bool,
+    pub orbit: OrbitMode,
+    pub orbit_speed: f32,
+    pub orbit_range: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            fov: FOV_DEGREES,
+            near: NEAR_PLANE,
+            far: FAR_PLANE,
+            position: [0.0, 0.0, 0.0],
+            target: None,
+            roll: 0.0,
+            orthographic: false,
+            orbit: OrbitMode::Off,
+            orbit_speed: DEFAULT_ORBIT_SPEED,
+            orbit_range: DEFAULT_ORBIT_RANGE,
+        }
+    }
+}
+
+impl CameraConfig {
+    pub fn from_js(value: &JsValue) -> Self {
+        let mut config = Self::default();
+        if !value.is_object() {
+            return config;
+        }
+
+        if let Some(v) = get_f32(value, "fov") {
+            config.fov = v;
+        }
+        if let Some(v) = get_f32(value, "near") {
+            config.near = v;
+        }
+        if let Some(v) = get_f32(value, "far") {
+            config.far = v;
+        }
+        if let Some(v) = get_vec3(value, "position") {
+            config.position = v;
+        }
+        if let Some(v) = get_vec3(value, "target") {
+            config.target = Some(v);
+        }
+        if let Some(v) = get_f32(value, "roll") {
+            config.roll = v;
+        }
+        if let Some(v) = get_bool(value, "orthographic") {
+            config.orthographic = v;
+        }
+        if let Some(mode) = get(value, "orbit")
+            .and_then(|v| v.as_string())
+            .and_then(|s| OrbitMode::parse(&s))
+        {
+            config.orbit = mode;
+        }
+        if let Some(v) = get_f32(value, "orbitSpeed") {
+            config.orbit_speed = v;
+        }
+        if let Some(v) = get_f32(value, "orbitRange") {
+            config.orbit_range = v;
+        }
+        config
+    }
+}
+
+/// Camera state: configuration plus the eased orbit angles (radians).
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub config: CameraConfig,
+    /// `config.target` or the default derived from the flake depth.
+    target: [f32; 3],
+    yaw: f32,
+    pitch: f32,
+    target_yaw: f32,
+    target_pitch: f32,
+}
+
+impl Camera {
+    pub fn new(config: CameraConfig, depth: f32) -> Self {
+        let mut camera = Self {
+            config,
+            target: [0.0; 3],
+            yaw: 0.0,
+            pitch: 0.0,
+            target_yaw: 0.0,
+            target_pitch: 0.0,
+        };
+        camera.set_depth(depth);
+        camera
+    }
+
+    /// Moves the default target along with the flake depth.
+    pub fn set_depth(&mut self, depth: f32) {
+        self.target = self.config.target.unwrap_or([0.0, 0.0, -depth / 2.0]);
+    }
+
+    pub fn set_target(&mut self, target: [f32; 3]) {
+        self.config.target = Some(target);
+        self.target = target;
+    }
+
+    /// Sets the orbit angles to ease towards, as fractions of `orbit_range` in [-1, 1].
+    pub fn aim(&mut self, yaw: f32, pitch: f32) {
+        let range = self.config.orbit_range.to_radians();
+        self.target_yaw = yaw.clamp(-1.0, 1.0) * range;
+        self.target_pitch = pitch.clamp(-1.0, 1.0) * range;
+    }
+
+    /// Advances the orbit. Returns true if the view changed.
+    pub fn update(&mut self, delta: f32) -> bool {
+        match self.config.orbit {
+            OrbitMode::Off => {
+                let moved = self.yaw != 0.0 || self.pitch != 0.0;
+                self.yaw = 0.0;
+                self.pitch = 0.0;
+                moved
+            }
+            OrbitMode::Auto => {
+                self.yaw = (self.yaw + self.config.orbit_speed.to_radians() * delta)
+                    % std::f32::consts::TAU;
+                true
+            }
+            OrbitMode::Scroll | OrbitMode::Orientation => {
+                let (dy, dp) = (self.target_yaw - self.yaw, self.target_pitch - self.pitch);
+                if dy.abs() < 1e-5 && dp.abs() < 1e-5 {
+                    return false;
+                }
+                self.yaw += dy * ORBIT_EASING;
+                self.pitch += dp * ORBIT_EASING;
+                true
+            }
+        }
+    }
+
+    /// Eye position after applying the orbit around the target.
+    pub fn eye(&self) -> [f32; 3] {
+        let (p, t) = (self.config.position, self.target);
+        let offset = sub(p, t);
+        let radius = length(offset);
+        if radius < 1e-6 {
+            return p;
+        }
+
+        let limit = 89f32.to_radians();
+        let azimuth = offset[0].atan2(offset[2]) + self.yaw;
+        let elevation = ((offset[1] / radius).asin() + self.pitch).clamp(-limit, limit);
+        let (sa, ca) = azimuth.sin_cos();
+        let (se, ce) = elevation.sin_cos();
+        [
+            t[0] + radius * ce * sa,
+            t[1] + radius * se,
+            t[2] + radius * ce * ca,
+        ]
+    }
+
+    pub fn view(&self) -> Mat4 {
+        look_at(self.eye(), self.target, self.config.roll.to_radians())
+    }
+
+    /// Right, up and backward axes of the view in world space.
+    pub fn basis(&self) -> [[f32; 3]; 3] {
+        let view = self.view();
+        [
+            [view[0], view[4], view[8]],
+            [view[1], view[5], view[9]],
+            [view[2], view[6], view[10]],
+        ]
+    }
+
+    pub fn projection(&self, aspect: f32) -> Mat4 {
+        let c = &self.config;
+        if c.orthographic {
+            let half_height = WORLD_HEIGHT;
+            orthographic(half_height * aspect, half_height, c.near, c.far)
+        } else {
+            perspective(c.fov.to_radians(), aspect, c.near, c.far)
+        }
+    }
+
+    /// World Y `share` of the way from the view center down to the bottom
+    /// edge of the screen, at the depth of the target.
+    pub fn floor(&self, share: f32) -> f32 {
+        let half_height = if self.config.orthographic {
+            WORLD_HEIGHT
+        } else {
+            let distance = length(sub(self.config.position, self.target));
+            distance * (self.config.fov.to_radians() / 2.0).tan()
+        };
+        self.target[1] - half_height * share
+    }
+
+    /// Scale applied to `a_size` before the perspective divide. Orthographic
+    /// sizes match the perspective ones at the distance where both views
+    /// cover the same height.
+    pub fn point_scale(&self, viewport_height: f32) -> f32 {
+        let scale = viewport_height * 0.015;
+        if self.config.orthographic {
+            let reference = WORLD_HEIGHT / (self.config.fov.to_radians() / 2.0).tan();
+            scale / reference
+        } else {
+            scale
+        }
+    }
+}
+
+pub fn perspective(fov: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+    let f = 1.0 / (fov / 2.0).tan();
+    [
+        f / aspect,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        f,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        (far + near) / (near - far),
+        -1.0,
+        0.0,
+        0.0,
+        (2.0 * far * near) / (near - far),
+        0.0,
+    ]
+}
+
+pub fn orthographic(half_width: f32, half_height: f32, near: f32, far: f32) -> Mat4 {
+    [
+        1.0 / half_width,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        1.0 / half_height,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        2.0 / (near - far),
+        0.0,
+        0.0,
+        0.0,
+        (far + near) / (near - far),
+        1.0,
+    ]
+}
+
+/// View matrix looking from `eye` at `target`, rolled around the view axis.
+pub fn look_at(eye: [f32; 3], target: [f32; 3], roll: f32) -> Mat4 {
+    let f = normalize(sub(target, eye));
+    let up = if length(cross(f, [0.0, 1.0, 0.0])) < 1e-6 {
+        [0.0, 0.0, -1.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    let s = normalize(cross(f, up));
+    let u = cross(s, f);
+
+    let (sr, cr) = roll.sin_cos();
+    let s = add(scale(s, cr), scale(u, sr));
+    let u = cross(s, f);
+
+    [
+        s[0],
+        u[0],
+        -f[0],
+        0.0,
+        s[1],
+        u[1],
+        -f[1],
+        0.0,
+        s[2],
+        u[2],
+        -f[2],
+        0.0,
+        -dot(s, eye),
+        -dot(u, eye),
+        dot(f, eye),
+        1.0,
+    ]
+}
+
+pub fn multiply(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut out = [0.0; 16];
+    for col in 0..4 {
+        for row in 0..4 {
+            out[col * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum();
+        }
+    }
+    out
+}
+
+/// `m * v` for a column vector.
+pub fn transform(m: &Mat4, v: [f32; 4]) -> [f32; 4] {
+    let mut out = [0.0; 4];
+    for (row, value) in out.iter_mut().enumerate() {
+        *value = (0..4).map(|k| m[k * 4 + row] * v[k]).sum();
+    }
+    out
+}
+
+pub fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+pub fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+pub fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let len = length(a);
+    if len > 0.0 {
+        scale(a, 1.0 / len)
+    } else {
+        a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: Mat4 = [
+        1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+    ];
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-4, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn look_at_down_negative_z_is_identity() {
+        assert_close(&look_at([0.0; 3], [0.0, 0.0, -1.0], 0.0), &IDENTITY);
+    }
+
+    #[test]
+    fn look_at_moves_eye_to_origin() {
+        let view = look_at([1.0, 2.0, 3.0], [1.0, 2.0, 0.0], 0.0);
+        assert_close(
+            &transform(&view, [1.0, 2.0, 3.0, 1.0]),
+            &[0.0, 0.0, 0.0, 1.0],
+        );
+        assert_close(
+            &transform(&view, [1.0, 2.0, 0.0, 1.0]),
+            &[0.0, 0.0, -3.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn look_at_along_x() {
+        // Looking down +X, world +Z points to the right
+        let view = look_at([0.0; 3], [1.0, 0.0, 0.0], 0.0);
+        assert_close(
+            &transform(&view, [0.0, 0.0, 1.0, 0.0]),
+            &[1.0, 0.0, 0.0, 0.0],
+        );
+        assert_close(
+            &transform(&view, [1.0, 0.0, 0.0, 0.0]),
+            &[0.0, 0.0, -1.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn look_at_rolls_around_view_axis() {
+        let view = look_at([0.0; 3], [0.0, 0.0, -1.0], std::f32::consts::FRAC_PI_2);
+        assert_close(
+            &transform(&view, [0.0, 1.0, 0.0, 0.0]),
+            &[1.0, 0.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn look_at_straight_down_stays_finite() {
+        let view = look_at([0.0, 10.0, 0.0], [0.0; 3], 0.0);
+        assert!(view.iter().all(|v| v.is_finite()));
+        assert_close(&transform(&view, [0.0; 4]), &[0.0; 4]);
+    }
+
+    #[test]
+    fn perspective_maps_planes_to_clip_range() {
+        let projection = perspective(90f32.to_radians(), 2.0, 1.0, 100.0);
+        let near = transform(&projection, [0.0, 0.0, -1.0, 1.0]);
+        let far = transform(&projection, [0.0, 0.0, -100.0, 1.0]);
+        assert!((near[2] / near[3] + 1.0).abs() < 1e-4);
+        assert!((far[2] / far[3] - 1.0).abs() < 1e-4);
+
+        // A 90 degree view reaches the top edge at y == distance
+        let edge = transform(&projection, [4.0, 2.0, -2.0, 1.0]);
+        assert_close(&[edge[0] / edge[3], edge[1] / edge[3]], &[1.0, 1.0]);
+    }
+
+    #[test]
+    fn orthographic_maps_box_to_clip_cube() {
+        let projection = orthographic(4.0, 2.0, 1.0, 11.0);
+        assert_close(
+            &transform(&projection, [4.0, -2.0, -1.0, 1.0]),
+            &[1.0, -1.0, -1.0, 1.0],
+        );
+        assert_close(
+            &transform(&projection, [-4.0, 2.0, -11.0, 1.0]),
+            &[-1.0, 1.0, 1.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn multiply_by_identity() {
+        let projection = perspective(1.0, 1.5, 0.1, 200.0);
+        assert_close(&multiply(&IDENTITY, &projection), &projection);
+        assert_close(&multiply(&projection, &IDENTITY), &projection);
+    }
+
+    #[test]
+    fn default_orbit_pivots_inside_world_box() {
+        let mut camera = Camera::new(CameraConfig::default(), DEFAULT_DEPTH);
+        assert_close(&camera.eye(), &[0.0; 3]);
+        camera.yaw = std::f32::consts::PI;
+        assert_close(&camera.eye(), &[0.0, 0.0, -DEFAULT_DEPTH]);
+    }
+
+    #[test]
+    fn default_target_follows_depth() {
+        let mut camera = Camera::new(CameraConfig::default(), 40.0);
+        camera.yaw = std::f32::consts::PI;
+        assert_close(&camera.eye(), &[0.0, 0.0, -40.0]);
+
+        camera.set_depth(80.0);
+        assert_close(&camera.eye(), &[0.0, 0.0, -80.0]);
+    }
+
+    #[test]
+    fn floor_is_on_screen_at_target_depth() {
+        for orthographic in [false, true] {
+            let config = CameraConfig {
+                orthographic,
+                ..CameraConfig::default()
+            };
+            let camera = Camera::new(config, DEFAULT_DEPTH);
+            let floor = camera.floor(FLOOR_LEVEL);
+            let clip = multiply(&camera.projection(1.0), &camera.view());
+            let [_, y, z, w] = transform(&clip, [0.0, floor, -DEFAULT_DEPTH / 2.0, 1.0]);
+            let (y, z) = (y / w, z / w);
+            assert!((y + FLOOR_LEVEL).abs() < 1e-4, "{orthographic}: {y}");
+            assert!((-1.0..=1.0).contains(&z), "{orthographic}: {z}");
+        }
+    }
+
+    #[test]
+    fn explicit_target_ignores_depth() {
+        let config = CameraConfig {
+            target: Some([0.0, 0.0, -10.0]),
+            ..CameraConfig::default()
+        };
+        let mut camera = Camera::new(config, 200.0);
+        camera.set_depth(60.0);
+        camera.yaw = std::f32::consts::PI;
+        assert_close(&camera.eye(), &[0.0, 0.0, -20.0]);
+    }
+}
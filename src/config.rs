@@ -1,12 +1,28 @@
 //! Snowfall configuration
 
+use crate::background::Background;
+use crate::camera::CameraConfig;
 use crate::constants::*;
+use crate::emitter::EmitterConfig;
+use crate::globe::GlobeConfig;
+use crate::ground::GroundConfig;
+use crate::hooks::ShaderHooks;
+use crate::mask::MaskConfig;
+use crate::palette::Palette;
+use crate::postprocess::PostConfig;
+use crate::precipitation::Precipitation;
+use crate::procedural::ProceduralConfig;
+use crate::texture::{AtlasEntry, SpriteSheet, TextureOptions, TextureSource};
+use crate::theme::{BlendMode, Shape, Theme, ThemeMotion};
 use wasm_bindgen::prelude::*;
-use web_sys::HtmlImageElement;
 
 #[wasm_bindgen]
 #[derive(Clone, Debug)]
 pub struct SnowConfig {
+    pub precipitation: Precipitation,
+    pub theme: Theme,
+    pub shape: Shape,
+    pub blend: BlendMode,
     pub particle_count: u32,
     pub gravity: f32,
     pub depth: f32,
@@ -17,17 +33,86 @@ pub struct SnowConfig {
     pub direction_x: f32,
     pub direction_y: f32,
     pub rotation_speed: f32,
-    
+    pub wind_angle: f32,
+    pub wind_elevation: f32,
+    pub wind_strength: f32,
+    pub scroll_parallax: f32,
+    pub focal_distance: f32,
+    pub aperture: f32,
+    pub streak_length: f32,
+    pub tumble: f32,
+    /// Depth of the per-particle brightness pulse, 0 disables it.
+    pub twinkle: f32,
+    /// Strength of glints as flakes rotate their facets to the light.
+    pub sparkle: f32,
+
     #[wasm_bindgen(getter_with_clone)]
     pub color: Option<Vec<f32>>,
-    
-    #[wasm_bindgen(getter_with_clone)]
-    pub texture: Option<HtmlImageElement>,
+
+    #[wasm_bindgen(skip)]
+    pub texture: Option<TextureSource>,
+
+    #[wasm_bindgen(skip)]
+    pub texture_options: TextureOptions,
+
+    /// GLSL replacing the `displace` and `shade` shader functions.
+    #[wasm_bindgen(skip)]
+    pub shader_hooks: ShaderHooks,
+
+    /// Animates `texture` as a grid of frames.
+    #[wasm_bindgen(skip)]
+    pub sprite_sheet: Option<SpriteSheet>,
+
+    #[wasm_bindgen(skip)]
+    pub camera: CameraConfig,
+
+    #[wasm_bindgen(skip)]
+    pub fog: FogConfig,
+
+    /// Overrides the theme palette.
+    #[wasm_bindgen(skip)]
+    pub palette: Option<Palette>,
+
+    /// Several shapes sharing one texture, used instead of `texture`.
+    #[wasm_bindgen(skip)]
+    pub textures: Vec<AtlasEntry>,
+
+    /// Generated crystal atlas, used when no textures are given.
+    #[wasm_bindgen(skip)]
+    pub procedural: Option<ProceduralConfig>,
+
+    /// Bloom and vignette applied after the particles are drawn.
+    #[wasm_bindgen(skip)]
+    pub postprocess: PostConfig,
+
+    /// Colors or image drawn behind the particles, transparent by default.
+    #[wasm_bindgen(skip)]
+    pub background: Background,
+
+    /// Ground plane at the floor, hiding flakes that reach it.
+    #[wasm_bindgen(skip)]
+    pub ground: Option<GroundConfig>,
+
+    /// Snow globe confining the flakes to a sphere.
+    #[wasm_bindgen(skip)]
+    pub globe: Option<GlobeConfig>,
+
+    /// Screen areas kept free of flakes, such as body text.
+    #[wasm_bindgen(skip)]
+    pub mask: Option<MaskConfig>,
+
+    /// Spawns flakes from a screen region instead of filling the world box.
+    #[wasm_bindgen(skip)]
+    pub emitter: Option<EmitterConfig>,
 }
 
 impl Default for SnowConfig {
     fn default() -> Self {
         Self {
+            precipitation: Precipitation::Snow,
+            theme: Theme::Snow,
+            shape: Shape::Circle,
+            blend: BlendMode::Normal,
             particle_count: DEFAULT_PARTICLE_COUNT,
             gravity: DEFAULT_GRAVITY,
             depth: DEFAULT_DEPTH,
@@ -38,8 +123,32 @@ impl Default for SnowConfig {
             direction_x: DEFAULT_DIRECTION_X,
             direction_y: DEFAULT_DIRECTION_Y,
             rotation_speed: DEFAULT_ROTATION_SPEED,
+            wind_angle: DEFAULT_WIND_ANGLE,
+            wind_elevation: DEFAULT_WIND_ELEVATION,
+            wind_strength: DEFAULT_WIND_STRENGTH,
+            scroll_parallax: DEFAULT_SCROLL_PARALLAX,
+            focal_distance: DEFAULT_FOCAL_DISTANCE,
+            aperture: DEFAULT_APERTURE,
+            streak_length: DEFAULT_STREAK_LENGTH,
+            tumble: 0.0,
+            twinkle: 0.0,
+            sparkle: 0.0,
             color: None,
             texture: None,
+            texture_options: TextureOptions::default(),
+            sprite_sheet: None,
+            shader_hooks: ShaderHooks::default(),
+            camera: CameraConfig::default(),
+            fog: FogConfig::default(),
+            palette: None,
+            textures: Vec::new(),
+            procedural: None,
+            postprocess: PostConfig::default(),
+            background: Background::default(),
+            ground: None,
+            globe: None,
+            mask: None,
+            emitter: None,
         }
     }
 }
@@ -53,22 +162,89 @@ impl SnowConfig {
 }
 
 impl SnowConfig {
-    pub fn from_js(value: JsValue) -> Self {
-        let mut config = Self::default();
+    /// Switches the precipitation type, resetting the fields it provides
+    /// defaults for.
+    pub fn apply_precipitation(&mut self, precipitation: Precipitation) {
+        let preset = precipitation.preset();
+        self.precipitation = precipitation;
+        self.gravity = preset.gravity;
+        self.speed_x = preset.speed_x;
+        self.speed_y = preset.speed_y;
+        self.min_size = preset.min_size;
+        self.min_alpha = preset.min_alpha;
+        self.rotation_speed = preset.rotation_speed;
+        self.streak_length = preset.streak_length;
+    }
 
-        if !value.is_object() {
-            if let Some(count) = value.as_f64() {
-                config.particle_count = count as u32;
+    /// Switches the particle theme: shape, tumbling, blending and fall
+    /// parameters. Themes keeping the precipitation motion restore it, so
+    /// nothing is left over from a previous theme.
+    pub fn apply_theme(&mut self, theme: Theme) {
+        let preset = theme.preset();
+        self.theme = theme;
+        self.shape = preset.shape;
+        self.tumble = preset.tumble;
+        self.blend = preset.blend;
+        let motion = preset.motion.unwrap_or_else(|| {
+            let fall = self.precipitation.preset();
+            ThemeMotion {
+                gravity: fall.gravity,
+                speed_y: fall.speed_y,
+                direction_y: DEFAULT_DIRECTION_Y,
+                min_size: fall.min_size,
+                min_alpha: fall.min_alpha,
+                rotation_speed: fall.rotation_speed,
             }
-            return config;
+        });
+        self.gravity = motion.gravity;
+        self.speed_y = motion.speed_y;
+        self.direction_y = motion.direction_y;
+        self.min_size = motion.min_size;
+        self.min_alpha = motion.min_alpha;
+        self.rotation_speed = motion.rotation_speed;
+    }
+
+    /// What the constructor draws with. Resolved the same way in release
+    /// and `configurable` builds.
+    pub fn appearance(&self) -> Appearance<'_> {
+        let texture = if !self.textures.is_empty() {
+            InitialTexture::Atlas(&self.textures)
+        } else if let Some(procedural) = self.procedural {
+            InitialTexture::Procedural(procedural)
+        } else if let Some(texture) = &self.texture {
+            InitialTexture::Single(texture)
+        } else {
+            InitialTexture::None
+        };
+        Appearance {
+            tint: match self.color.as_deref() {
+                Some([r, g, b, ..]) => Some([*r, *g, *b]),
+                _ => None,
+            },
+            texture,
+            blend: self.blend,
+        }
+    }
+
+    /// Applies the presets, then the numeric fields `field` returns by
+    /// their JS key, so explicit fields override the presets.
+    fn apply_fields(
+        &mut self,
+        precipitation: Option<Precipitation>,
+        theme: Option<Theme>,
+        field: impl Fn(&str) -> Option<f64>,
+    ) {
+        if let Some(precipitation) = precipitation {
+            self.apply_precipitation(precipitation);
+        }
+        if let Some(theme) = theme {
+            self.apply_theme(theme);
         }
 
         macro_rules! extract {
             ($field:ident, $key:expr, $ty:ty) => {
-                if let Ok(v) = js_sys::Reflect::get(&value, &$key.into()) {
-                    if let Some(num) = v.as_f64() {
-                        config.$field = num as $ty;
-                    }
+                if let Some(num) = field($key) {
+                    self.$field = num as $ty;
                 }
             };
         }
@@ -83,11 +259,60 @@ impl SnowConfig {
         extract!(direction_x, "directionX", f32);
         extract!(direction_y, "directionY", f32);
         extract!(rotation_speed, "rotationSpeed", f32);
-        
+        extract!(wind_angle, "windAngle", f32);
+        extract!(wind_elevation, "windElevation", f32);
+        extract!(wind_strength, "windStrength", f32);
+        extract!(scroll_parallax, "scrollParallax", f32);
+        extract!(focal_distance, "focalDistance", f32);
+        extract!(aperture, "aperture", f32);
+        extract!(streak_length, "streakLength", f32);
+        extract!(tumble, "tumble", f32);
+        extract!(twinkle, "twinkle", f32);
+        extract!(sparkle, "sparkle", f32);
+    }
+}
+
+impl SnowConfig {
+    pub fn from_js(value: JsValue) -> Self {
+        let mut config = Self::default();
+
+        if !value.is_object() {
+            if let Some(count) = value.as_f64() {
+                config.particle_count = count as u32;
+            }
+            return config;
+        }
+
+        let precipitation = get(&value, "precipitation")
+            .and_then(|v| v.as_string())
+            .and_then(|s| Precipitation::parse(&s));
+        let theme = get(&value, "theme")
+            .and_then(|v| v.as_string())
+            .and_then(|s| Theme::parse(&s));
+        config.apply_fields(precipitation, theme, |key| {
+            js_sys::Reflect::get(&value, &key.into())
+                .ok()
+                .and_then(|v| v.as_f64())
+        });
+
+        if let Some(shape) = get(&value, "shape")
+            .and_then(|v| v.as_string())
+            .and_then(|s| Shape::parse(&s))
+        {
+            config.shape = shape;
+        }
+        if let Some(blend) = get(&value, "blend")
+            .and_then(|v| v.as_string())
+            .and_then(|s| BlendMode::parse(&s))
+        {
+            config.blend = blend;
+        }
+
         if let Ok(color_val) = js_sys::Reflect::get(&value, &"color".into()) {
             if js_sys::Array::is_array(&color_val) {
                 let arr: js_sys::Array = color_val.into();
-                let vec: Vec<f32> = arr.iter()
+                let vec: Vec<f32> = arr
+                    .iter()
                     .filter_map(|x| x.as_f64().map(|n| n as f32))
                     .collect();
                 if !vec.is_empty() {
@@ -98,16 +323,210 @@ impl SnowConfig {
 
         if let Ok(tex_val) = js_sys::Reflect::get(&value, &"texture".into()) {
             if !tex_val.is_undefined() && !tex_val.is_null() {
-                if let Ok(img) = tex_val.dyn_into::<HtmlImageElement>() {
-                    config.texture = Some(img);
-                }
+                config.texture = TextureSource::from_js(&tex_val);
             }
         }
 
+        if let Some(options) = get(&value, "textureOptions") {
+            config.texture_options = TextureOptions::from_js(&options);
+        }
+
+        if let Some(sheet) = get(&value, "spriteSheet") {
+            config.sprite_sheet = SpriteSheet::from_js(&sheet);
+        }
+
+        if let Some(hooks) = get(&value, "shaderHooks") {
+            config.shader_hooks = ShaderHooks::from_js(&hooks);
+        }
+
+        if let Some(camera) = get(&value, "camera") {
+            config.camera = CameraConfig::from_js(&camera);
+        }
+
+        if let Some(fog) = get(&value, "fog") {
+            config.fog = FogConfig::from_js(&fog);
+        }
+
+        if let Some(palette) = get(&value, "palette") {
+            config.palette = Palette::from_js(&palette);
+        }
+
+        if let Some(textures) = get(&value, "textures") {
+            config.textures = AtlasEntry::parse_list(&textures);
+        }
+
+        if let Some(procedural) = get(&value, "procedural") {
+            config.procedural = ProceduralConfig::from_js(&procedural);
+        }
+
+        if let Some(postprocess) = get(&value, "postprocess") {
+            config.postprocess = PostConfig::from_js(&postprocess);
+        }
+
+        if let Some(background) = get(&value, "background") {
+            config.background = Background::from_js(&background);
+        }
+
+        if let Some(ground) = get(&value, "ground") {
+            config.ground = GroundConfig::from_js(&ground);
+        }
+
+        if let Some(globe) = get(&value, "globe") {
+            config.globe = GlobeConfig::from_js(&globe);
+        }
+
+        if let Some(mask) = get(&value, "mask") {
+            config.mask = MaskConfig::from_js(&mask);
+        }
+
+        if let Some(emitter) = get(&value, "emitter") {
+            config.emitter = EmitterConfig::from_js(&emitter);
+        }
+
+        config
+    }
+}
+
+/// Initial color tint, texture and blending of a config.
+#[derive(Clone, Debug)]
+pub struct Appearance<'a> {
+    /// `None` keeps the texture colors; colors with fewer than three
+    /// channels are ignored.
+    pub tint: Option<[f32; 3]>,
+    pub texture: InitialTexture<'a>,
+    pub blend: BlendMode,
+}
+
+/// Texture source the constructor uploads. An atlas wins over procedural
+/// crystals, which win over a single texture.
+#[derive(Clone, Debug)]
+pub enum InitialTexture<'a> {
+    None,
+    Single(&'a TextureSource),
+    Atlas(&'a [AtlasEntry]),
+    Procedural(ProceduralConfig),
+}
+
+/// How fog density grows with distance from the camera.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FogMode {
+    Off = 0,
+    Linear = 1,
+    Exponential = 2,
+    ExponentialSquared = 3,
+}
+
+impl FogMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "off" | "none" => Some(Self::Off),
+            "linear" => Some(Self::Linear),
+            "exp" | "exponential" => Some(Self::Exponential),
+            "exp2" | "exponentialSquared" => Some(Self::ExponentialSquared),
+            _ => None,
+        }
+    }
+}
+
+/// Distance fog blending far flakes into `color`.
+///
+/// Linear fog ramps between `near` and `far`; the exponential modes start at
+/// `near` and grow with `density`.
+#[derive(Clone, Copy, Debug)]
+pub struct FogConfig {
+    pub mode: FogMode,
+    pub color: [f32; 3],
+    pub near: f32,
+    pub far: f32,
+    pub density: f32,
+}
+
+impl Default for FogConfig {
+    fn default() -> Self {
+        Self {
+            mode: FogMode::Off,
+            color: DEFAULT_FOG_COLOR,
+            near: DEFAULT_FOG_NEAR,
+            far: DEFAULT_FOG_FAR,
+            density: DEFAULT_FOG_DENSITY,
+        }
+    }
+}
+
+impl FogConfig {
+    /// A fog object without `mode` enables linear fog.
+    pub fn from_js(value: &JsValue) -> Self {
+        let mut config = Self::default();
+        if !value.is_object() {
+            return config;
+        }
+
+        config.mode = get(value, "mode")
+            .and_then(|v| v.as_string())
+            .and_then(|s| FogMode::parse(&s))
+            .unwrap_or(FogMode::Linear);
+        if let Some(v) = get_vec3(value, "color") {
+            config.color = v;
+        }
+        config.set_range(
+            get_f32(value, "near").unwrap_or(config.near),
+            get_f32(value, "far").unwrap_or(config.far),
+        );
+        if let Some(v) = get_f32(value, "density") {
+            config.density = v;
+        }
         config
     }
+
+    /// Sets the linear fog range, pushing `far` past `near` if needed.
+    pub fn set_range(&mut self, near: f32, far: f32) {
+        self.near = near;
+        self.far = far.max(near + MIN_FOG_RANGE);
+    }
+}
+
+/// Reads `key` from a JS object, treating `undefined` and `null` as missing.
+pub(crate) fn get(value: &JsValue, key: &str) -> Option<JsValue> {
+    js_sys::Reflect::get(value, &key.into())
+        .ok()
+        .filter(|v| !v.is_undefined() && !v.is_null())
+}
+
+pub(crate) fn get_f32(value: &JsValue, key: &str) -> Option<f32> {
+    get(value, key)?.as_f64().map(|n| n as f32)
+}
+
+pub(crate) fn get_bool(value: &JsValue, key: &str) -> Option<bool> {
+    get(value, key)?.as_bool()
+}
+
+/// Reads a numeric array, skipping non-numeric entries.
+pub(crate) fn get_floats(value: &JsValue, key: &str) -> Option<Vec<f32>> {
+    let v = get(value, key)?;
+    if !js_sys::Array::is_array(&v) {
+        return None;
+    }
+    let arr: js_sys::Array = v.into();
+    Some(
+        arr.iter()
+            .filter_map(|x| x.as_f64().map(|n| n as f32))
+            .collect(),
+    )
+}
+
+pub(crate) fn get_vec3(value: &JsValue, key: &str) -> Option<[f32; 3]> {
+    match get_floats(value, key)?.as_slice() {
+        [x, y, z, ..] => Some([*x, *y, *z]),
+        _ => None,
+    }
 }
 
+/// Wind gusts blowing along a 3D direction.
+///
+/// `direction` is a unit vector built from the configured angle (in the XZ
+/// plane, 0° = +X, 90° = towards the camera) and elevation (positive values
+/// are updrafts). `current` is the gust drift accumulated along it.
 #[derive(Clone, Copy, Debug)]
 pub struct WindState {
     pub current: f32,
@@ -116,6 +535,8 @@ pub struct WindState {
     pub min: f32,
     pub max: f32,
     pub easing: f32,
+    pub direction: [f32; 3],
+    pub strength: f32,
 }
 
 impl Default for WindState {
@@ -127,6 +548,354 @@ impl Default for WindState {
             min: WIND_MIN,
             max: WIND_MAX,
             easing: WIND_EASING,
+            direction: wind_direction(DEFAULT_WIND_ANGLE, DEFAULT_WIND_ELEVATION),
+            strength: DEFAULT_WIND_STRENGTH,
         }
     }
 }
+
+impl WindState {
+    pub fn from_config(config: &SnowConfig) -> Self {
+        let mut wind = Self::default();
+        wind.configure(config);
+        wind
+    }
+
+    /// Applies the configured direction and strength. `direction_x` mirrors
+    /// the X component.
+    pub fn configure(&mut self, config: &SnowConfig) {
+        self.direction = wind_direction(config.wind_angle, config.wind_elevation);
+        self.direction[0] *= config.direction_x;
+        self.strength = config.wind_strength;
+    }
+
+    /// Accumulated gust drift as a vector.
+    pub fn drift(&self) -> [f32; 3] {
+        let [x, y, z] = self.direction;
+        let d = self.current * self.strength;
+        [x * d, y * d, z * d]
+    }
+
+    /// Rate of change of `drift`, as integrated by the renderer.
+    pub fn drift_rate(&self) -> [f32; 3] {
+        let [x, y, z] = self.velocity();
+        [x * 0.5, y * 0.5, z * 0.5]
+    }
+
+    /// Instantaneous gust velocity. Flips when the gust changes sign.
+    pub fn velocity(&self) -> [f32; 3] {
+        let [x, y, z] = self.direction;
+        let v = self.force * self.strength;
+        [x * v, y * v, z * v]
+    }
+
+    /// Heading of the current gust in degrees, in the XZ plane.
+    pub fn angle(&self) -> f32 {
+        let [x, _, z] = self.velocity();
+        z.atan2(x).to_degrees()
+    }
+
+    /// Vertical angle of the current gust in degrees.
+    pub fn elevation(&self) -> f32 {
+        let [x, y, z] = self.velocity();
+        y.atan2(x.hypot(z)).to_degrees()
+    }
+
+    /// Magnitude of the current gust.
+    pub fn magnitude(&self) -> f32 {
+        (self.force * self.strength).abs()
+    }
+}
+
+/// Unit vector for a wind angle and elevation given in degrees.
+pub fn wind_direction(angle: f32, elevation: f32) -> [f32; 3] {
+    let (sa, ca) = angle.to_radians().sin_cos();
+    let (se, ce) = elevation.to_radians().sin_cos();
+    [ca * ce, se, sa * ce]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::precipitation::PrecipitationPreset;
+    use crate::theme::ThemePreset;
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-5, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn wind_direction_follows_angle_and_elevation() {
+        assert_close(wind_direction(0.0, 0.0), [1.0, 0.0, 0.0]);
+        assert_close(wind_direction(90.0, 0.0), [0.0, 0.0, 1.0]);
+        assert_close(wind_direction(180.0, 0.0), [-1.0, 0.0, 0.0]);
+        assert_close(wind_direction(0.0, 90.0), [0.0, 1.0, 0.0]);
+        assert_close(wind_direction(45.0, -90.0), [0.0, -1.0, 0.0]);
+        for (angle, elevation) in [(30.0, 20.0), (-135.0, 60.0), (400.0, -10.0)] {
+            let [x, y, z] = wind_direction(angle, elevation);
+            assert!(((x * x + y * y + z * z).sqrt() - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn wind_configure_applies_direction_and_strength() {
+        let mut config = SnowConfig {
+            wind_angle: 90.0,
+            wind_elevation: 0.0,
+            wind_strength: 2.5,
+            ..SnowConfig::default()
+        };
+        let mut wind = WindState::from_config(&config);
+        assert_close(wind.direction, [0.0, 0.0, 1.0]);
+        assert_eq!(wind.strength, 2.5);
+
+        config.wind_angle = 0.0;
+        config.direction_x = -1.0;
+        wind.configure(&config);
+        assert_close(wind.direction, [-1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn wind_drift_scales_with_gust_and_strength() {
+        let mut wind = WindState {
+            direction: wind_direction(0.0, 90.0),
+            strength: 2.0,
+            ..WindState::default()
+        };
+        assert_close(wind.drift(), [0.0; 3]);
+
+        wind.current = 1.5;
+        wind.force = -0.1;
+        assert_close(wind.drift(), [0.0, 3.0, 0.0]);
+        assert_close(wind.velocity(), [0.0, -0.2, 0.0]);
+        assert_close(wind.drift_rate(), [0.0, -0.1, 0.0]);
+        assert!((wind.magnitude() - 0.2).abs() < 1e-6);
+        assert!((wind.elevation() + 90.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn wind_angle_flips_with_gust() {
+        let mut wind = WindState {
+            direction: wind_direction(90.0, 0.0),
+            ..WindState::default()
+        };
+        wind.force = 0.1;
+        assert!((wind.angle() - 90.0).abs() < 1e-3);
+        wind.force = -0.1;
+        assert!((wind.angle() + 90.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn default_config_is_plain_snow() {
+        let config = SnowConfig::default();
+        assert_eq!(config.precipitation, Precipitation::Snow);
+        assert_eq!(config.theme, Theme::Snow);
+        assert_eq!(config.shape, Shape::Circle);
+        assert_eq!(config.blend, BlendMode::Normal);
+        assert_eq!(config.particle_count, DEFAULT_PARTICLE_COUNT);
+        assert_eq!(config.depth, DEFAULT_DEPTH);
+        assert_eq!(config.fog.mode, FogMode::Off);
+        assert!(config.color.is_none());
+        assert!(config.texture.is_none());
+        assert!(config.textures.is_empty());
+        assert!(config.procedural.is_none());
+        assert!(config.palette.is_none());
+        assert_eq!(config.tumble, 0.0);
+        assert_eq!(config.twinkle, 0.0);
+        assert_eq!(config.sparkle, 0.0);
+    }
+
+    #[test]
+    fn default_config_matches_snow_presets() {
+        let mut config = SnowConfig::default();
+        config.apply_precipitation(Precipitation::Snow);
+        config.apply_theme(Theme::Snow);
+        let defaults = SnowConfig::default();
+        assert_eq!(config.gravity, defaults.gravity);
+        assert_eq!(config.speed_x, defaults.speed_x);
+        assert_eq!(config.speed_y, defaults.speed_y);
+        assert_eq!(config.direction_y, defaults.direction_y);
+        assert_eq!(config.min_size, defaults.min_size);
+        assert_eq!(config.min_alpha, defaults.min_alpha);
+        assert_eq!(config.rotation_speed, defaults.rotation_speed);
+        assert_eq!(config.streak_length, defaults.streak_length);
+    }
+
+    fn raw_texture() -> TextureSource {
+        TextureSource::Raw {
+            data: vec![255; 4],
+            width: 1,
+            height: 1,
+        }
+    }
+
+    #[test]
+    fn appearance_defaults_to_untinted_circles() {
+        let config = SnowConfig::default();
+        let appearance = config.appearance();
+        assert_eq!(appearance.tint, None);
+        assert!(matches!(appearance.texture, InitialTexture::None));
+        assert_eq!(appearance.blend, BlendMode::Normal);
+    }
+
+    #[test]
+    fn appearance_tints_with_three_channels() {
+        let mut config = SnowConfig {
+            color: Some(vec![0.2, 0.4, 0.6, 1.0]),
+            ..SnowConfig::default()
+        };
+        assert_eq!(config.appearance().tint, Some([0.2, 0.4, 0.6]));
+
+        config.color = Some(vec![0.2, 0.4]);
+        assert_eq!(config.appearance().tint, None);
+        config.color = Some(Vec::new());
+        assert_eq!(config.appearance().tint, None);
+    }
+
+    #[test]
+    fn appearance_keeps_theme_blend() {
+        let mut config = SnowConfig::default();
+        config.apply_theme(Theme::Embers);
+        assert_eq!(config.appearance().blend, BlendMode::Additive);
+    }
+
+    #[test]
+    fn appearance_prefers_atlas_then_procedural_then_texture() {
+        let mut config = SnowConfig {
+            texture: Some(raw_texture()),
+            ..SnowConfig::default()
+        };
+        assert!(matches!(
+            config.appearance().texture,
+            InitialTexture::Single(TextureSource::Raw { width: 1, .. })
+        ));
+
+        let procedural = ProceduralConfig::default();
+        config.procedural = Some(procedural);
+        assert!(matches!(
+            config.appearance().texture,
+            InitialTexture::Procedural(p) if p == procedural
+        ));
+
+        config.textures = vec![
+            AtlasEntry {
+                source: raw_texture(),
+                weight: 1.0,
+            };
+            2
+        ];
+        assert!(matches!(
+            config.appearance().texture,
+            InitialTexture::Atlas(entries) if entries.len() == 2
+        ));
+    }
+
+    #[test]
+    fn fog_range_keeps_far_past_near() {
+        let mut fog = FogConfig::default();
+        fog.set_range(50.0, 50.0);
+        assert_eq!(fog.near, 50.0);
+        assert!(fog.far > fog.near);
+
+        fog.set_range(80.0, 10.0);
+        assert!(fog.far > fog.near);
+
+        fog.set_range(10.0, 80.0);
+        assert_eq!((fog.near, fog.far), (10.0, 80.0));
+    }
+
+    #[test]
+    fn precipitation_parses_every_name() {
+        for (name, precipitation) in [
+            ("snow", Precipitation::Snow),
+            ("rain", Precipitation::Rain),
+            ("sleet", Precipitation::Sleet),
+            ("hail", Precipitation::Hail),
+        ] {
+            assert_eq!(Precipitation::parse(name), Some(precipitation));
+        }
+        assert_eq!(Precipitation::parse("drizzle"), None);
+        assert_eq!(Precipitation::parse("Rain"), None);
+        assert_eq!(Precipitation::parse(""), None);
+    }
+
+    #[test]
+    fn precipitation_sets_preset_fields() {
+        let mut config = SnowConfig::default();
+        config.apply_precipitation(Precipitation::Rain);
+        assert_eq!(config.precipitation, Precipitation::Rain);
+        assert_eq!(config.gravity, PrecipitationPreset::RAIN.gravity);
+        assert_eq!(config.speed_x, PrecipitationPreset::RAIN.speed_x);
+        assert_eq!(config.min_size, PrecipitationPreset::RAIN.min_size);
+        assert_eq!(
+            config.streak_length,
+            PrecipitationPreset::RAIN.streak_length
+        );
+        assert!(config.streak_length > 0.0);
+
+        config.apply_precipitation(Precipitation::Hail);
+        assert_eq!(config.streak_length, 0.0);
+        assert!(config.precipitation.preset().bounce > 0.0);
+
+        config.apply_precipitation(Precipitation::Sleet);
+        let sleet = config.precipitation.preset();
+        assert!(sleet.streak_share > 0.0 && sleet.streak_share < 1.0);
+        assert_eq!(sleet.bounce, 0.0);
+
+        config.apply_precipitation(Precipitation::Snow);
+        let defaults = SnowConfig::default();
+        assert_eq!(config.gravity, defaults.gravity);
+        assert_eq!(config.speed_y, defaults.speed_y);
+        assert_eq!(config.streak_length, defaults.streak_length);
+    }
+
+    #[test]
+    fn explicit_fields_override_presets() {
+        let mut config = SnowConfig::default();
+        config.apply_fields(Some(Precipitation::Rain), None, |key| match key {
+            "gravity" => Some(5.0),
+            "streakLength" => Some(0.0),
+            _ => None,
+        });
+        assert_eq!(config.precipitation, Precipitation::Rain);
+        assert_eq!(config.gravity, 5.0);
+        assert_eq!(config.streak_length, 0.0);
+        assert_eq!(config.speed_x, PrecipitationPreset::RAIN.speed_x);
+    }
+
+    #[test]
+    fn theme_overrides_precipitation_and_fields_override_theme() {
+        let mut config = SnowConfig::default();
+        config.apply_fields(Some(Precipitation::Rain), Some(Theme::Embers), |key| {
+            (key == "minAlpha").then_some(0.9)
+        });
+        let embers = ThemePreset::EMBERS.motion.unwrap();
+        assert_eq!(config.gravity, embers.gravity);
+        assert_eq!(config.direction_y, embers.direction_y);
+        assert_eq!(config.min_alpha, 0.9);
+    }
+
+    #[test]
+    fn snow_theme_restores_precipitation_motion() {
+        let mut config = SnowConfig::default();
+        config.apply_precipitation(Precipitation::Hail);
+        let before = config.clone();
+
+        config.apply_theme(Theme::Embers);
+        assert_eq!(config.direction_y, -1.0);
+        assert_eq!(config.blend, BlendMode::Additive);
+
+        config.apply_theme(Theme::Snow);
+        assert_eq!(config.shape, before.shape);
+        assert_eq!(config.tumble, before.tumble);
+        assert_eq!(config.blend, before.blend);
+        assert_eq!(config.gravity, before.gravity);
+        assert_eq!(config.speed_y, before.speed_y);
+        assert_eq!(config.direction_y, before.direction_y);
+        assert_eq!(config.min_size, before.min_size);
+        assert_eq!(config.min_alpha, before.min_alpha);
+        assert_eq!(config.rotation_speed, before.rotation_speed);
+    }
+}
@@ -14,6 +14,20 @@ pub const DEFAULT_SPEED_Y: f32 = 0.6;
 pub const DEFAULT_DIRECTION_X: f32 = 1.0;
 pub const DEFAULT_DIRECTION_Y: f32 = 1.0;
 pub const DEFAULT_ROTATION_SPEED: f32 = 1.7;
+pub const DEFAULT_SCROLL_PARALLAX: f32 = 0.0;
+pub const DEFAULT_STREAK_LENGTH: f32 = 0.0;
+
+// Depth of field
+pub const DEFAULT_FOCAL_DISTANCE: f32 = 40.0;
+pub const DEFAULT_APERTURE: f32 = 0.0;
+
+// Fog
+pub const DEFAULT_FOG_COLOR: [f32; 3] = [0.086, 0.106, 0.137];
+pub const DEFAULT_FOG_NEAR: f32 = 20.0;
+pub const DEFAULT_FOG_FAR: f32 = 120.0;
+/// Smallest gap between linear fog `near` and `far`, which the shaders divide by.
+pub const MIN_FOG_RANGE: f32 = 1e-3;
+pub const DEFAULT_FOG_DENSITY: f32 = 0.02;
 
 // Wind
 pub const WIND_FORCE_INITIAL: f32 = 0.09;
@@ -21,12 +35,92 @@ pub const WIND_TARGET_INITIAL: f32 = 0.05;
 pub const WIND_MIN: f32 = 0.05;
 pub const WIND_MAX: f32 = 0.15;
 pub const WIND_EASING: f32 = 0.003;
+pub const DEFAULT_WIND_ANGLE: f32 = 0.0;
+pub const DEFAULT_WIND_ELEVATION: f32 = 0.0;
+pub const DEFAULT_WIND_STRENGTH: f32 = 1.0;
 
 // Camera
 pub const FOV_DEGREES: f32 = 45.0;
 pub const NEAR_PLANE: f32 = 0.1;
 pub const FAR_PLANE: f32 = 200.0;
 pub const WORLD_HEIGHT: f32 = 110.0;
+pub const DEFAULT_ORBIT_SPEED: f32 = 2.0;
+pub const DEFAULT_ORBIT_RANGE: f32 = 15.0;
+pub const ORBIT_EASING: f32 = 0.05;
+
+// Texture atlas
+pub const ATLAS_MIN_CELL_SIZE: u32 = 32;
+pub const ATLAS_MAX_CELL_SIZE: u32 = 256;
+pub const ATLAS_CELL_PADDING: u32 = 2;
+
+// Texture uploads
+pub const DEFAULT_TEXTURE_SIZE: u32 = 256;
+pub const TEXTURE_MAX_SIZE: u32 = 2048;
+pub const DEFAULT_ANISOTROPY: f32 = 4.0;
+pub const DEFAULT_SPRITE_FPS: f32 = 12.0;
+
+// Post-processing
+pub const DEFAULT_BLOOM_THRESHOLD: f32 = 0.6;
+pub const DEFAULT_POST_SCALE: f32 = 0.5;
+pub const POST_MIN_SCALE: f32 = 0.125;
+pub const BLOOM_BLUR_PASSES: u32 = 2;
+pub const POST_TEXTURE_UNIT: u32 = 2; // and 3 for the bloom buffer
+pub const BACKGROUND_TEXTURE_UNIT: u32 = 4;
+
+// Ground
+/// Floor height below the camera target, as a share of the screen's half-height there.
+pub const FLOOR_LEVEL: f32 = 0.8;
+pub const DEFAULT_GROUND_COLOR: [f32; 3] = [0.16, 0.15, 0.14];
+pub const DEFAULT_SNOW_COVER_COLOR: [f32; 3] = [0.92, 0.94, 0.98];
+pub const DEFAULT_GROUND_ACCUMULATION: f32 = 120.0;
+/// Half size of the ground plane, far enough to reach the horizon.
+pub const GROUND_EXTENT: f32 = 20000.0;
+/// Depth at which snow patches fade to an even cover.
+pub const GROUND_DETAIL_DISTANCE: f32 = 600.0;
+
+// Snow globe
+pub const GLOBE_DISTANCE: f32 = 100.0;
+pub const GLOBE_RADIUS: f32 = 35.0;
+pub const DEFAULT_GLOBE_DAMPING: f32 = 0.6;
+pub const DEFAULT_GLOBE_GLASS: f32 = 0.5;
+pub const GLOBE_MAX_ENERGY: f32 = 1.5;
+/// Swirl speed in radians per second at full energy.
+pub const GLOBE_SWIRL_SPEED: f32 = 2.5;
+/// Accelerometer change in m/s² ignored as hand tremor.
+pub const GLOBE_MOTION_THRESHOLD: f32 = 3.0;
+pub const GLOBE_MOTION_SCALE: f32 = 0.05;
+
+// Occlusion masks
+pub const MASK_TEXTURE_UNIT: u32 = 1;
+/// Mask resolution per CSS pixel.
+pub const MASK_SCALE: f32 = 0.5;
+pub const DEFAULT_MASK_FEATHER: f32 = 8.0;
+
+// Formations
+pub const DEFAULT_FORMATION_COUNT: u32 = 4000;
+/// Share of the view a formation spans.
+pub const DEFAULT_FORMATION_SIZE: f32 = 0.6;
+/// Distance from the camera to the formation plane.
+pub const FORMATION_DISTANCE: f32 = 60.0;
+pub const DEFAULT_FORMATION_GATHER: f32 = 2.5;
+pub const DEFAULT_FORMATION_HOLD: f32 = 4.0;
+pub const DEFAULT_FORMATION_RELEASE: f32 = 2.0;
+pub const DEFAULT_FORMATION_FONT: &str = "bold 160px sans-serif";
+/// Longest side images are scaled to before sampling.
+pub const FORMATION_RESOLUTION: u32 = 256;
+
+// Emitters
+pub const DEFAULT_EMITTER_RATE: f32 = 300.0;
+pub const DEFAULT_EMITTER_LIFETIME: f32 = 15.0;
+/// Circle and cursor radius in CSS pixels.
+pub const DEFAULT_EMITTER_RADIUS: f32 = 40.0;
+/// Nearest spawn distance from the camera; the farthest is the world depth.
+pub const EMITTER_MIN_DISTANCE: f32 = 20.0;
+
+// Procedural crystals
+pub const DEFAULT_PROCEDURAL_COUNT: u32 = 8;
+pub const DEFAULT_PROCEDURAL_SIZE: u32 = 64;
+pub const PROCEDURAL_MAX_COUNT: u32 = 64;
 
 // Feature flag
 pub const RUNTIME_CONFIGURABLE: bool = cfg!(feature = "configurable");
@@ -0,0 +1,346 @@
+//! Emitters spawning flakes from a screen region instead of the world box
+
+use crate::camera::Camera;
+use crate::config::{get, get_f32};
+use crate::constants::*;
+use js_sys::Math;
+use std::ops::Range;
+use wasm_bindgen::prelude::*;
+use web_sys::{Element, HtmlCanvasElement, WebGlBuffer, WebGlRenderingContext as GL};
+
+/// Spawn time of flakes waiting in the pool, far in the future.
+const UNSPAWNED: f32 = 1.0e9;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmitterShape {
+    /// Horizontal line, the top edge of the canvas by default.
+    #[default]
+    Line = 0,
+    Point = 1,
+    Rect = 2,
+    Circle = 3,
+    /// Circle around the position fed through `set_cursor_position`.
+    Cursor = 4,
+}
+
+impl EmitterShape {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "line" => Some(Self::Line),
+            "point" => Some(Self::Point),
+            "rect" => Some(Self::Rect),
+            "circle" => Some(Self::Circle),
+            "cursor" => Some(Self::Cursor),
+            _ => None,
+        }
+    }
+}
+
+/// Where and how fast flakes spawn. Coordinates are CSS pixels relative to
+/// the canvas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmitterConfig {
+    pub shape: EmitterShape,
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    /// Measured on every spawn instead of `x`, `y`, `width` and `height`.
+    pub element: Option<Element>,
+    /// Circle and cursor radius.
+    pub radius: f32,
+    /// Flakes per second.
+    pub rate: f32,
+    /// Seconds until a flake vanishes.
+    pub lifetime: f32,
+    /// Outward speed flakes start with, slowed down by drag.
+    pub spread: f32,
+}
+
+impl Default for EmitterConfig {
+    fn default() -> Self {
+        Self {
+            shape: EmitterShape::default(),
+            x: None,
+            y: None,
+            width: None,
+            height: None,
+            element: None,
+            radius: DEFAULT_EMITTER_RADIUS,
+            rate: DEFAULT_EMITTER_RATE,
+            lifetime: DEFAULT_EMITTER_LIFETIME,
+            spread: 0.0,
+        }
+    }
+}
+
+impl EmitterConfig {
+    /// Accepts a shape name or `{ shape, x, y, width, height, element,
+    /// radius, rate, lifetime, spread }`.
+    pub fn from_js(value: &JsValue) -> Option<Self> {
+        if let Some(name) = value.as_string() {
+            return Some(Self {
+                shape: EmitterShape::parse(&name)?,
+                ..Self::default()
+            });
+        }
+        if !value.is_object() {
+            return None;
+        }
+        let mut config = Self::default();
+        if let Some(shape) = get(value, "shape")
+            .and_then(|v| v.as_string())
+            .and_then(|s| EmitterShape::parse(&s))
+        {
+            config.shape = shape;
+        }
+        config.x = get_f32(value, "x");
+        config.y = get_f32(value, "y");
+        config.width = get_f32(value, "width").map(|v| v.max(0.0));
+        config.height = get_f32(value, "height").map(|v| v.max(0.0));
+        config.element = get(value, "element").and_then(|v| v.dyn_into().ok());
+        if let Some(v) = get_f32(value, "radius") {
+            config.radius = v.max(0.0);
+        }
+        if let Some(v) = get_f32(value, "rate") {
+            config.rate = v.max(0.0);
+        }
+        if let Some(v) = get_f32(value, "lifetime") {
+            config.lifetime = v.max(0.1);
+        }
+        if let Some(v) = get_f32(value, "spread") {
+            config.spread = v.max(0.0);
+        }
+        Some(config)
+    }
+
+    /// `[x, y, width, height]` of the element or the configured region.
+    /// Lines default to the top edge, other shapes to the whole canvas.
+    fn region(&self, canvas: &HtmlCanvasElement) -> [f32; 4] {
+        if let Some(element) = &self.element {
+            let (rect, origin) = (
+                element.get_bounding_client_rect(),
+                canvas.get_bounding_client_rect(),
+            );
+            return [
+                (rect.x() - origin.x()) as f32,
+                (rect.y() - origin.y()) as f32,
+                rect.width() as f32,
+                rect.height() as f32,
+            ];
+        }
+        let (x, y) = (self.x.unwrap_or(0.0), self.y.unwrap_or(0.0));
+        let default_height = match self.shape {
+            EmitterShape::Line => 0.0,
+            _ => canvas.client_height() as f32 - y,
+        };
+        [
+            x,
+            y,
+            self.width.unwrap_or(canvas.client_width() as f32 - x),
+            self.height.unwrap_or(default_height),
+        ]
+    }
+
+    /// Random spawn point on the screen within `region`. Lines run along
+    /// the bottom of the region, so flakes fall out from under an element.
+    fn sample(&self, region: [f32; 4], cursor: [f32; 2]) -> [f32; 2] {
+        let rand = || Math::random() as f32;
+        let [x, y, width, height] = region;
+        let center = [x + width / 2.0, y + height / 2.0];
+        match self.shape {
+            EmitterShape::Line => [x + rand() * width, y + height],
+            EmitterShape::Point => center,
+            EmitterShape::Rect => [x + rand() * width, y + rand() * height],
+            EmitterShape::Circle => disc(center, self.radius),
+            EmitterShape::Cursor => disc(cursor, self.radius),
+        }
+    }
+}
+
+/// Uniformly distributed point within `radius` of `center`.
+fn disc(center: [f32; 2], radius: f32) -> [f32; 2] {
+    let angle = Math::random() as f32 * std::f32::consts::TAU;
+    let r = radius * (Math::random() as f32).sqrt();
+    [center[0] + angle.cos() * r, center[1] + angle.sin() * r]
+}
+
+/// View and scene state spawn points are projected with.
+pub struct SpawnSpace<'a> {
+    pub camera: &'a Camera,
+    pub canvas: &'a HtmlCanvasElement,
+    /// Farthest spawn distance from the camera.
+    pub depth: f32,
+    pub time: f32,
+    /// Accumulated gust drift, `WindState::drift`.
+    pub wind: [f32; 3],
+    /// Last cursor position in CSS pixels.
+    pub cursor: Option<[f32; 2]>,
+}
+
+impl SpawnSpace<'_> {
+    /// World position `distance` in front of the camera under a screen
+    /// point.
+    fn project(&self, point: [f32; 2], distance: f32) -> [f32; 3] {
+        let (width, height) = (
+            (self.canvas.client_width() as f32).max(1.0),
+            (self.canvas.client_height() as f32).max(1.0),
+        );
+        let (ndc_x, ndc_y) = (point[0] / width * 2.0 - 1.0, 1.0 - point[1] / height * 2.0);
+        let config = &self.camera.config;
+        let half_height = if config.orthographic {
+            WORLD_HEIGHT
+        } else {
+            distance * (config.fov.to_radians() / 2.0).tan()
+        };
+        let half_width = half_height * width / height;
+        let [right, up, back] = self.camera.basis();
+        let eye = self.camera.eye();
+        [0, 1, 2].map(|i| {
+            eye[i] + right[i] * ndc_x * half_width + up[i] * ndc_y * half_height
+                - back[i] * distance
+        })
+    }
+}
+
+/// Pool of flakes respawned in turn. Spawn positions and times replace the
+/// particle positions as `a_spawn`.
+pub struct Emitter {
+    /// Next particle to respawn.
+    next: usize,
+    /// Share of a flake left over from the last frame.
+    carry: f32,
+    /// `a_speed.x` of every particle, to cancel the gust drift from before
+    /// a flake spawned.
+    wind_response: Vec<f32>,
+}
+
+impl Emitter {
+    pub fn new(wind_response: Vec<f32>) -> Self {
+        Self {
+            next: 0,
+            carry: 0.0,
+            wind_response,
+        }
+    }
+
+    /// `a_spawn` data for a pool where no flake has spawned yet.
+    pub fn pool(count: usize) -> Vec<f32> {
+        [0.0, 0.0, 0.0, UNSPAWNED].repeat(count)
+    }
+
+    /// Flakes due after `delta` seconds at `rate` per second.
+    pub fn due(&mut self, delta: f32, rate: f32) -> usize {
+        self.carry += delta.max(0.0) * rate;
+        let count = self.carry.floor();
+        self.carry -= count;
+        count as usize
+    }
+
+    /// Respawns the `count` oldest flakes of `buffer` at `space.time`.
+    pub fn spawn(
+        &mut self,
+        gl: &GL,
+        buffer: &WebGlBuffer,
+        config: &EmitterConfig,
+        space: &SpawnSpace,
+        count: usize,
+    ) {
+        let pool = self.wind_response.len();
+        let count = count.min(pool);
+        if count == 0 {
+            return;
+        }
+        let cursor = match (config.shape, space.cursor) {
+            (EmitterShape::Cursor, None) => return,
+            (_, cursor) => cursor.unwrap_or_default(),
+        };
+        let region = config.region(space.canvas);
+        let rand = || Math::random() as f32;
+        let mut data = Vec::with_capacity(count * 4);
+        for i in 0..count {
+            let index = (self.next + i) % pool;
+            let point = config.sample(region, cursor);
+            let distance =
+                EMITTER_MIN_DISTANCE + rand() * (space.depth - EMITTER_MIN_DISTANCE).max(0.0);
+            let position = space.project(point, distance);
+            let response = self.wind_response[index];
+            for (axis, value) in position.iter().enumerate() {
+                data.push(value - space.wind[axis] * response);
+            }
+            data.push(space.time);
+        }
+
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(buffer));
+        for (start, flakes) in wrap_chunks(self.next, count, pool) {
+            if flakes.is_empty() {
+                continue;
+            }
+            unsafe {
+                let array = js_sys::Float32Array::view(&data[flakes.start * 4..flakes.end * 4]);
+                gl.buffer_sub_data_with_i32_and_array_buffer_view(
+                    GL::ARRAY_BUFFER,
+                    (start * 16) as i32,
+                    &array,
+                );
+            }
+        }
+        self.next = (self.next + count) % pool;
+    }
+}
+
+/// Splits `count` writes starting at pool index `next` where they wrap
+/// around the end of a `pool`-sized buffer. Returns the pool index each
+/// part starts at and its range within the written flakes; the second part
+/// is empty unless the writes wrap.
+fn wrap_chunks(next: usize, count: usize, pool: usize) -> [(usize, Range<usize>); 2] {
+    let first = count.min(pool - next);
+    [(next, 0..first), (0, first..count)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_starts_unspawned() {
+        let pool = Emitter::pool(3);
+        assert_eq!(pool.len(), 12);
+        assert!(pool
+            .chunks_exact(4)
+            .all(|flake| flake == [0.0, 0.0, 0.0, UNSPAWNED]));
+        assert!(Emitter::pool(0).is_empty());
+    }
+
+    #[test]
+    fn due_spawns_rate_times_delta() {
+        let mut emitter = Emitter::new(vec![0.0; 10]);
+        assert_eq!(emitter.due(0.5, 300.0), 150);
+        assert_eq!(emitter.due(1.0 / 60.0, 300.0), 5);
+        assert_eq!(emitter.due(0.0, 300.0), 0);
+        assert_eq!(emitter.due(-1.0, 300.0), 0);
+        assert_eq!(emitter.due(1.0, 0.0), 0);
+    }
+
+    #[test]
+    fn due_carries_fractions_across_frames() {
+        let mut emitter = Emitter::new(vec![0.0; 10]);
+        // A quarter of a flake per frame
+        let counts: Vec<usize> = (0..8).map(|_| emitter.due(0.125, 2.0)).collect();
+        assert_eq!(counts, [0, 0, 0, 1, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn wrap_chunks_without_wrap() {
+        assert_eq!(wrap_chunks(2, 5, 10), [(2, 0..5), (0, 5..5)]);
+        assert_eq!(wrap_chunks(5, 5, 10), [(5, 0..5), (0, 5..5)]);
+    }
+
+    #[test]
+    fn wrap_chunks_splits_at_pool_end() {
+        assert_eq!(wrap_chunks(8, 5, 10), [(8, 0..2), (0, 2..5)]);
+        assert_eq!(wrap_chunks(9, 10, 10), [(9, 0..1), (0, 1..10)]);
+        assert_eq!(wrap_chunks(0, 10, 10), [(0, 0..10), (0, 10..10)]);
+    }
+}
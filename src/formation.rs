@@ -0,0 +1,401 @@
+//! Formations: flakes gathering into text or an image before falling again
+
+use crate::camera::Camera;
+use crate::config::{get, get_f32};
+use crate::constants::*;
+use crate::texture::{create_canvas, TextureSource};
+use js_sys::Math;
+use wasm_bindgen::prelude::*;
+use web_sys::{CanvasRenderingContext2d, WebGlBuffer, WebGlRenderingContext as GL};
+
+/// Location of `a_target`, after the particle attributes in the renderer.
+pub(crate) const TARGET_LOCATION: u32 = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormationOptions {
+    /// Flakes taking part, capped at the particle count.
+    pub count: u32,
+    /// Share of the view width or height the shape spans, whichever is
+    /// reached first.
+    pub size: f32,
+    /// Distance from the camera to the shape.
+    pub distance: f32,
+    /// Seconds to drift into place.
+    pub gather: f32,
+    /// Seconds to hold the shape, 0 holds it until released.
+    pub hold: f32,
+    /// Seconds to fall back into the snowfall.
+    pub release: f32,
+    /// CSS font for text.
+    pub font: String,
+}
+
+impl Default for FormationOptions {
+    fn default() -> Self {
+        Self {
+            count: DEFAULT_FORMATION_COUNT,
+            size: DEFAULT_FORMATION_SIZE,
+            distance: FORMATION_DISTANCE,
+            gather: DEFAULT_FORMATION_GATHER,
+            hold: DEFAULT_FORMATION_HOLD,
+            release: DEFAULT_FORMATION_RELEASE,
+            font: DEFAULT_FORMATION_FONT.to_string(),
+        }
+    }
+}
+
+impl FormationOptions {
+    /// Reads `{ count, size, distance, gather, hold, release, font }`,
+    /// keeping defaults for missing keys.
+    pub fn from_js(value: &JsValue) -> Self {
+        let mut options = Self::default();
+        if let Some(v) = get_f32(value, "count") {
+            options.count = v.max(1.0) as u32;
+        }
+        if let Some(v) = get_f32(value, "size") {
+            options.size = v.clamp(0.05, 1.0);
+        }
+        if let Some(v) = get_f32(value, "distance") {
+            options.distance = v.max(1.0);
+        }
+        if let Some(v) = get_f32(value, "gather") {
+            options.gather = v.max(0.0);
+        }
+        if let Some(v) = get_f32(value, "hold") {
+            options.hold = v.max(0.0);
+        }
+        if let Some(v) = get_f32(value, "release") {
+            options.release = v.max(0.0);
+        }
+        if let Some(font) = get(value, "font").and_then(|v| v.as_string()) {
+            options.font = font;
+        }
+        options
+    }
+}
+
+/// Opaque pixels of a rasterized shape.
+pub struct Silhouette {
+    /// Pixel centers, Y growing downwards.
+    points: Vec<[f32; 2]>,
+    width: f32,
+    height: f32,
+}
+
+impl Silhouette {
+    /// Renders `text` centered, one line per `\n`.
+    pub fn from_text(text: &str, font: &str) -> Result<Self, JsValue> {
+        let lines: Vec<&str> = text.lines().collect();
+        let (canvas, ctx) = create_canvas(1, 1)?;
+        ctx.set_font(font);
+        let mut width: f64 = 0.0;
+        let (mut ascent, mut descent): (f64, f64) = (0.0, 0.0);
+        for line in &lines {
+            let metrics = ctx.measure_text(line)?;
+            width = width.max(metrics.width());
+            ascent = ascent.max(metrics.font_bounding_box_ascent());
+            descent = descent.max(metrics.font_bounding_box_descent());
+        }
+        let line_height = ascent + descent;
+        let (width, height) = (
+            (width.ceil() as u32).max(1),
+            ((line_height * lines.len() as f64).ceil() as u32).max(1),
+        );
+
+        // Resizing resets the context state
+        canvas.set_width(width);
+        canvas.set_height(height);
+        ctx.set_font(font);
+        ctx.set_text_align("center");
+        ctx.set_text_baseline("alphabetic");
+        ctx.set_fill_style_str("#000");
+        for (i, line) in lines.iter().enumerate() {
+            let y = i as f64 * line_height + ascent;
+            ctx.fill_text(line, width as f64 / 2.0, y)?;
+        }
+        Self::from_canvas(&ctx, width, height)
+    }
+
+    /// Samples the alpha channel of a loaded image.
+    pub fn from_image(image: &TextureSource) -> Result<Self, JsValue> {
+        if !image.is_ready() {
+            return Err("Formation image is still loading".into());
+        }
+        let (width, height) = image.size();
+        if width == 0 || height == 0 {
+            return Err("Formation image has no size".into());
+        }
+        let scale = FORMATION_RESOLUTION as f32 / width.max(height) as f32;
+        let (width, height) = (
+            ((width as f32 * scale).round() as u32).max(1),
+            ((height as f32 * scale).round() as u32).max(1),
+        );
+        let (_canvas, ctx) = create_canvas(width, height)?;
+        image.draw(&ctx, 0.0, 0.0, width as f64, height as f64)?;
+        Self::from_canvas(&ctx, width, height)
+    }
+
+    fn from_canvas(
+        ctx: &CanvasRenderingContext2d,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, JsValue> {
+        let data = ctx
+            .get_image_data(0.0, 0.0, width as f64, height as f64)?
+            .data();
+        let points: Vec<[f32; 2]> = data
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(_, pixel)| pixel[3] >= 128)
+            .map(|(i, _)| {
+                let i = i as u32;
+                [(i % width) as f32 + 0.5, (i / width) as f32 + 0.5]
+            })
+            .collect();
+        if points.is_empty() {
+            return Err("Formation shape is empty".into());
+        }
+        Ok(Self {
+            points,
+            width: width as f32,
+            height: height as f32,
+        })
+    }
+
+    /// `a_target` data for `particles` flakes: the first `options.count`
+    /// get a random point of the shape, facing the camera, and a random
+    /// start delay; the rest a negative `w` to stay out.
+    pub fn targets(
+        &self,
+        options: &FormationOptions,
+        camera: &Camera,
+        aspect: f32,
+        particles: usize,
+    ) -> Vec<f32> {
+        let [right, up, back] = camera.basis();
+        let eye = camera.eye();
+        let center = [0, 1, 2].map(|i| eye[i] - back[i] * options.distance);
+
+        let half_height = if camera.config.orthographic {
+            WORLD_HEIGHT
+        } else {
+            options.distance * (camera.config.fov.to_radians() / 2.0).tan()
+        };
+        let half_width = half_height * aspect;
+        let scale = options.size * 2.0 * (half_width / self.width).min(half_height / self.height);
+
+        let rand = || Math::random() as f32;
+        let count = (options.count as usize).min(particles);
+        let mut targets = Vec::with_capacity(particles * 4);
+        for _ in 0..count {
+            let index = ((rand() * self.points.len() as f32) as usize).min(self.points.len() - 1);
+            let [px, py] = self.points[index];
+            // Jitter within the pixel so flakes sharing one don't overlap
+            let x = (px + rand() - 0.5 - self.width / 2.0) * scale;
+            let y = (self.height / 2.0 - py - rand() + 0.5) * scale;
+            for i in 0..3 {
+                targets.push(center[i] + right[i] * x + up[i] * y);
+            }
+            targets.push(rand());
+        }
+        for _ in count..particles {
+            targets.extend_from_slice(&[0.0, 0.0, 0.0, -1.0]);
+        }
+        targets
+    }
+}
+
+/// Shape being formed, with its targets bound to `TARGET_LOCATION`.
+pub struct Formation {
+    buffer: WebGlBuffer,
+    timeline: Timeline,
+}
+
+impl Formation {
+    /// Uploads `targets` from `Silhouette::targets` and binds them.
+    pub fn new(
+        gl: &GL,
+        targets: &[f32],
+        options: &FormationOptions,
+        start: f32,
+    ) -> Result<Self, JsValue> {
+        let buffer = gl.create_buffer().ok_or("Failed to create buffer")?;
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&buffer));
+        unsafe {
+            let array = js_sys::Float32Array::view(targets);
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &array, GL::STATIC_DRAW);
+        }
+        gl.enable_vertex_attrib_array(TARGET_LOCATION);
+        gl.vertex_attrib_pointer_with_i32(TARGET_LOCATION, 4, GL::FLOAT, false, 0, 0);
+        Ok(Self {
+            buffer,
+            timeline: Timeline::new(options, start),
+        })
+    }
+
+    /// 0 while the flakes fall freely, 1 while the shape is complete.
+    pub fn progress(&self, time: f32) -> f32 {
+        self.timeline.progress(time)
+    }
+
+    /// Starts falling apart at `time` unless already doing so.
+    pub fn release(&mut self, time: f32) {
+        self.timeline.release(time);
+    }
+
+    pub fn is_finished(&self, time: f32) -> bool {
+        self.timeline.is_finished(time)
+    }
+
+    /// Leaves `a_target` constant with a negative `w`, so every flake
+    /// stays out of formation.
+    pub fn delete(&self, gl: &GL) {
+        gl.disable_vertex_attrib_array(TARGET_LOCATION);
+        gl.vertex_attrib4f(TARGET_LOCATION, 0.0, 0.0, 0.0, -1.0);
+        gl.delete_buffer(Some(&self.buffer));
+    }
+}
+
+/// Gather, hold and release phases of a formation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Timeline {
+    /// Render time the flakes set off at.
+    start: f32,
+    gather: f32,
+    hold: f32,
+    release: f32,
+    /// Time `release` was called.
+    released: Option<f32>,
+}
+
+impl Timeline {
+    fn new(options: &FormationOptions, start: f32) -> Self {
+        Self {
+            start,
+            gather: options.gather,
+            hold: options.hold,
+            release: options.release,
+            released: None,
+        }
+    }
+
+    /// When the flakes start falling again, if known yet.
+    fn release_start(&self) -> Option<f32> {
+        self.released
+            .or((self.hold > 0.0).then_some(self.start + self.gather + self.hold))
+    }
+
+    fn progress(&self, time: f32) -> f32 {
+        match self.release_start() {
+            Some(release) => {
+                let gathered = ramp(time.min(release) - self.start, self.gather);
+                gathered * (1.0 - ramp(time - release, self.release))
+            }
+            None => ramp(time - self.start, self.gather),
+        }
+    }
+
+    fn release(&mut self, time: f32) {
+        match self.release_start() {
+            Some(release) if release <= time => {}
+            _ => self.released = Some(time),
+        }
+    }
+
+    fn is_finished(&self, time: f32) -> bool {
+        self.release_start()
+            .is_some_and(|release| time >= release + self.release)
+    }
+}
+
+/// Share of `duration` elapsed, 0-1.
+fn ramp(elapsed: f32, duration: f32) -> f32 {
+    if duration > 0.0 {
+        (elapsed / duration).clamp(0.0, 1.0)
+    } else if elapsed >= 0.0 {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeline(gather: f32, hold: f32, release: f32) -> Timeline {
+        let options = FormationOptions {
+            gather,
+            hold,
+            release,
+            ..FormationOptions::default()
+        };
+        Timeline::new(&options, 10.0)
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{a} != {b}");
+    }
+
+    #[test]
+    fn gathers_holds_and_releases() {
+        let timeline = timeline(2.0, 3.0, 4.0);
+        assert_eq!(timeline.progress(9.0), 0.0);
+        assert_eq!(timeline.progress(10.0), 0.0);
+        assert_close(timeline.progress(11.0), 0.5);
+        assert_eq!(timeline.progress(12.0), 1.0);
+        assert_eq!(timeline.progress(15.0), 1.0);
+        assert_close(timeline.progress(16.0), 0.75);
+        assert_eq!(timeline.progress(19.0), 0.0);
+        assert!(!timeline.is_finished(18.9));
+        assert!(timeline.is_finished(19.0));
+    }
+
+    #[test]
+    fn zero_hold_waits_for_release() {
+        let mut timeline = timeline(2.0, 0.0, 4.0);
+        assert_eq!(timeline.progress(1000.0), 1.0);
+        assert!(!timeline.is_finished(1000.0));
+
+        timeline.release(1000.0);
+        assert_close(timeline.progress(1002.0), 0.5);
+        assert!(!timeline.is_finished(1003.9));
+        assert!(timeline.is_finished(1004.0));
+    }
+
+    #[test]
+    fn release_while_gathering_falls_from_partial_shape() {
+        let mut timeline = timeline(2.0, 3.0, 4.0);
+        timeline.release(11.0);
+        assert_close(timeline.progress(11.0), 0.5);
+        assert_close(timeline.progress(13.0), 0.25);
+        assert_eq!(timeline.progress(15.0), 0.0);
+        assert!(timeline.is_finished(15.0));
+    }
+
+    #[test]
+    fn release_after_scheduled_release_is_ignored() {
+        let mut timeline = timeline(2.0, 3.0, 4.0);
+        timeline.release(17.0);
+        assert_close(timeline.progress(17.0), 0.5);
+        assert!(timeline.is_finished(19.0));
+    }
+
+    #[test]
+    fn zero_durations_jump() {
+        let timeline = timeline(0.0, 1.0, 0.0);
+        assert_eq!(timeline.progress(9.9), 0.0);
+        assert_eq!(timeline.progress(10.0), 1.0);
+        assert_eq!(timeline.progress(11.0), 0.0);
+        assert!(timeline.is_finished(11.0));
+    }
+
+    #[test]
+    fn ramp_clamps() {
+        assert_eq!(ramp(-1.0, 2.0), 0.0);
+        assert_eq!(ramp(1.0, 2.0), 0.5);
+        assert_eq!(ramp(3.0, 2.0), 1.0);
+        assert_eq!(ramp(-0.1, 0.0), 0.0);
+        assert_eq!(ramp(0.0, 0.0), 1.0);
+    }
+}
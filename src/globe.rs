@@ -0,0 +1,218 @@
+//! Snow globe: flakes confined to a sphere that settle and swirl when shaken
+
+use crate::camera::{transform, Camera};
+use crate::config::{get_f32, get_vec3};
+use crate::constants::*;
+use crate::postprocess::{Pass, Quad};
+use crate::shaders::GLASS_SHADER_SOURCE;
+use wasm_bindgen::prelude::*;
+use web_sys::WebGlRenderingContext as GL;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlobeConfig {
+    pub center: [f32; 3],
+    pub radius: f32,
+    /// Share of the shake energy lost per second.
+    pub damping: f32,
+    /// Strength of the glass highlights and the vignette around it, 0-1.
+    pub glass: f32,
+}
+
+impl Default for GlobeConfig {
+    fn default() -> Self {
+        Self {
+            center: [0.0, 0.0, -GLOBE_DISTANCE],
+            radius: GLOBE_RADIUS,
+            damping: DEFAULT_GLOBE_DAMPING,
+            glass: DEFAULT_GLOBE_GLASS,
+        }
+    }
+}
+
+impl GlobeConfig {
+    /// Accepts `true` for defaults or `{ center, radius, damping, glass }`.
+    pub fn from_js(value: &JsValue) -> Option<Self> {
+        if value.as_bool() == Some(true) {
+            return Some(Self::default());
+        }
+        if !value.is_object() {
+            return None;
+        }
+        let mut config = Self::default();
+        if let Some(v) = get_vec3(value, "center") {
+            config.center = v;
+        }
+        if let Some(v) = get_f32(value, "radius") {
+            config.radius = v.max(0.1);
+        }
+        if let Some(v) = get_f32(value, "damping") {
+            config.damping = v.clamp(0.0, 0.99);
+        }
+        if let Some(v) = get_f32(value, "glass") {
+            config.glass = v.clamp(0.0, 1.0);
+        }
+        Some(config)
+    }
+}
+
+/// Shake energy and the swirl it drives.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GlobeState {
+    /// 0 when settled, 1 lifts every flake.
+    pub energy: f32,
+    /// Swirl angle in radians.
+    pub swirl: f32,
+    /// Last accelerometer reading, to detect changes.
+    last_motion: Option<[f32; 3]>,
+}
+
+impl GlobeState {
+    pub fn shake(&mut self, strength: f32) {
+        self.energy = (self.energy + strength.max(0.0)).min(GLOBE_MAX_ENERGY);
+    }
+
+    /// Turns a jolt between accelerometer readings (m/s²) into a shake.
+    pub fn motion(&mut self, acceleration: [f32; 3]) {
+        if let Some(last) = self.last_motion {
+            let jolt = (0..3)
+                .map(|i| (acceleration[i] - last[i]).powi(2))
+                .sum::<f32>()
+                .sqrt();
+            if jolt > GLOBE_MOTION_THRESHOLD {
+                self.shake((jolt - GLOBE_MOTION_THRESHOLD) * GLOBE_MOTION_SCALE);
+            }
+        }
+        self.last_motion = Some(acceleration);
+    }
+
+    /// Swirls with the current energy, then loses `damping` of it per second.
+    pub fn update(&mut self, delta: f32, damping: f32) {
+        self.swirl = (self.swirl + self.energy * GLOBE_SWIRL_SPEED * delta) % std::f32::consts::TAU;
+        self.energy *= (1.0 - damping).powf(delta);
+        if self.energy < 1e-3 {
+            self.energy = 0.0;
+        }
+    }
+}
+
+/// Draws the glass over the particles.
+pub struct GlassLayer {
+    quad: Quad,
+    pass: Pass,
+}
+
+impl GlassLayer {
+    pub fn new(gl: &GL) -> Result<Self, JsValue> {
+        Ok(Self {
+            quad: Quad::new(gl)?,
+            pass: Pass::new(
+                gl,
+                GLASS_SHADER_SOURCE,
+                &["u_globeCircle", "u_aspect", "u_glass"],
+            )?,
+        })
+    }
+
+    /// Outline of the globe on screen: NDC center and radius in NDC height
+    /// units. `None` when the globe is behind the camera.
+    fn outline(globe: &GlobeConfig, camera: &Camera, aspect: f32) -> Option<[f32; 3]> {
+        let [x, y, z] = globe.center;
+        let center = transform(&camera.view(), [x, y, z, 1.0]);
+        let projection = camera.projection(aspect);
+        let clip = transform(&projection, center);
+        let [cx, cy, cz, _] = center;
+        let edge = transform(&projection, [cx, cy + globe.radius, cz, 1.0]);
+        if clip[3] <= 0.0 || edge[3] <= 0.0 {
+            return None;
+        }
+        let (ndc_x, ndc_y) = (clip[0] / clip[3], clip[1] / clip[3]);
+        Some([ndc_x, ndc_y, edge[1] / edge[3] - ndc_y])
+    }
+
+    /// Draws over the current framebuffer with straight-alpha blending.
+    /// Leaves the blend function changed and not the particle program.
+    pub fn draw(&self, gl: &GL, globe: &GlobeConfig, camera: &Camera, aspect: f32) {
+        if globe.glass <= 0.0 {
+            return;
+        }
+        let Some([x, y, radius]) = Self::outline(globe, camera, aspect) else {
+            return;
+        };
+        let pass = &self.pass;
+        gl.blend_func(GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA);
+        self.quad.bind(gl);
+        gl.use_program(Some(&pass.program));
+        gl.uniform3f(pass.uniform("u_globeCircle"), x, y, radius);
+        gl.uniform1f(pass.uniform("u_aspect"), aspect);
+        gl.uniform1f(pass.uniform("u_glass"), globe.glass);
+        Pass::draw(gl);
+        self.quad.unbind(gl);
+    }
+
+    pub fn delete(&self, gl: &GL) {
+        self.quad.delete(gl);
+        gl.delete_program(Some(&self.pass.program));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shake_is_capped() {
+        let mut state = GlobeState::default();
+        state.shake(1.0);
+        assert_eq!(state.energy, 1.0);
+        state.shake(1.0);
+        assert_eq!(state.energy, GLOBE_MAX_ENERGY);
+        state.shake(-5.0);
+        assert_eq!(state.energy, GLOBE_MAX_ENERGY);
+    }
+
+    #[test]
+    fn first_motion_only_records_reading() {
+        let mut state = GlobeState::default();
+        state.motion([0.0, 9.8, 50.0]);
+        assert_eq!(state.energy, 0.0);
+    }
+
+    #[test]
+    fn motion_below_threshold_is_ignored() {
+        let mut state = GlobeState::default();
+        state.motion([0.0, 9.8, 0.0]);
+        state.motion([GLOBE_MOTION_THRESHOLD, 9.8, 0.0]);
+        assert_eq!(state.energy, 0.0);
+    }
+
+    #[test]
+    fn motion_above_threshold_shakes() {
+        let mut state = GlobeState::default();
+        state.motion([0.0, 9.8, 0.0]);
+        state.motion([0.0, 9.8, GLOBE_MOTION_THRESHOLD + 10.0]);
+        assert!((state.energy - 10.0 * GLOBE_MOTION_SCALE).abs() < 1e-5);
+    }
+
+    #[test]
+    fn damping_settles_to_exactly_zero() {
+        let mut state = GlobeState::default();
+        state.shake(1.0);
+        state.update(1.0, 0.5);
+        assert!((state.energy - 0.5).abs() < 1e-6);
+        for _ in 0..60 {
+            state.update(1.0 / 6.0, 0.5);
+        }
+        assert_eq!(state.energy, 0.0);
+    }
+
+    #[test]
+    fn swirl_follows_energy() {
+        let mut state = GlobeState::default();
+        state.update(1.0, 0.5);
+        assert_eq!(state.swirl, 0.0);
+        state.shake(1.0);
+        state.update(0.1, 0.0);
+        assert!((state.swirl - GLOBE_SWIRL_SPEED * 0.1).abs() < 1e-6);
+        assert_eq!(state.energy, 1.0);
+    }
+}
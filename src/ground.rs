@@ -0,0 +1,178 @@
+//! Ground plane with accumulating snow cover
+
+use crate::camera::Camera;
+use crate::config::{get_f32, get_vec3, SnowConfig};
+use crate::constants::*;
+use crate::postprocess::{Pass, Quad};
+use crate::shaders::{GROUND_SHADER_SOURCE, GROUND_VERTEX_SHADER_SOURCE};
+use wasm_bindgen::prelude::*;
+use web_sys::WebGlRenderingContext as GL;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GroundConfig {
+    /// Bare ground color.
+    pub color: [f32; 3],
+    pub snow_color: [f32; 3],
+    /// Initial share of the ground covered with snow, 0-1.
+    pub cover: f32,
+    /// Seconds until the ground is fully white, 0 keeps `cover`.
+    pub accumulation: f32,
+}
+
+impl Default for GroundConfig {
+    fn default() -> Self {
+        Self {
+            color: DEFAULT_GROUND_COLOR,
+            snow_color: DEFAULT_SNOW_COVER_COLOR,
+            cover: 0.0,
+            accumulation: DEFAULT_GROUND_ACCUMULATION,
+        }
+    }
+}
+
+impl GroundConfig {
+    /// Accepts `true` for defaults or `{ color, snowColor, cover, accumulation }`.
+    pub fn from_js(value: &JsValue) -> Option<Self> {
+        if value.as_bool() == Some(true) {
+            return Some(Self::default());
+        }
+        if !value.is_object() {
+            return None;
+        }
+        let mut config = Self::default();
+        if let Some(v) = get_vec3(value, "color") {
+            config.color = v;
+        }
+        if let Some(v) = get_vec3(value, "snowColor") {
+            config.snow_color = v;
+        }
+        if let Some(v) = get_f32(value, "cover") {
+            config.cover = v.clamp(0.0, 1.0);
+        }
+        if let Some(v) = get_f32(value, "accumulation") {
+            config.accumulation = v.max(0.0);
+        }
+        Some(config)
+    }
+
+    /// Snow cover after `time` seconds.
+    pub fn cover_at(&self, time: f32) -> f32 {
+        if self.accumulation > 0.0 {
+            (self.cover + time / self.accumulation).min(1.0)
+        } else {
+            self.cover
+        }
+    }
+}
+
+/// Draws the ground under the camera, out to the horizon.
+pub struct GroundLayer {
+    quad: Quad,
+    pass: Pass,
+    /// Render time the snow cover started growing at.
+    start: f32,
+}
+
+impl GroundLayer {
+    pub fn new(gl: &GL, start: f32) -> Result<Self, JsValue> {
+        Ok(Self {
+            quad: Quad::new(gl)?,
+            pass: Pass::with_vertex(
+                gl,
+                GROUND_VERTEX_SHADER_SOURCE,
+                GROUND_SHADER_SOURCE,
+                &[
+                    "u_projection",
+                    "u_view",
+                    "u_groundY",
+                    "u_plane",
+                    "u_groundColor",
+                    "u_snowColor",
+                    "u_cover",
+                    "u_detailDistance",
+                    "u_fogMode",
+                    "u_fogParams",
+                    "u_fogColor",
+                ],
+            )?,
+            start,
+        })
+    }
+
+    /// Draws `config.ground` at `floor` over the current framebuffer.
+    /// Leaves blending on, but not the particle program.
+    pub fn draw(
+        &self,
+        gl: &GL,
+        config: &SnowConfig,
+        camera: &Camera,
+        aspect: f32,
+        floor: f32,
+        time: f32,
+    ) {
+        let Some(ground) = &config.ground else {
+            return;
+        };
+        let (pass, fog) = (&self.pass, &config.fog);
+        gl.disable(GL::BLEND);
+        self.quad.bind(gl);
+        gl.use_program(Some(&pass.program));
+
+        let projection = camera.projection(aspect);
+        gl.uniform_matrix4fv_with_f32_array(pass.uniform("u_projection"), false, &projection);
+        gl.uniform_matrix4fv_with_f32_array(pass.uniform("u_view"), false, &camera.view());
+        let [x, _, z] = camera.eye();
+        gl.uniform1f(pass.uniform("u_groundY"), floor);
+        gl.uniform3f(pass.uniform("u_plane"), x, z, GROUND_EXTENT);
+
+        let [r, g, b] = ground.color;
+        gl.uniform3f(pass.uniform("u_groundColor"), r, g, b);
+        let [r, g, b] = ground.snow_color;
+        gl.uniform3f(pass.uniform("u_snowColor"), r, g, b);
+        gl.uniform1f(pass.uniform("u_cover"), ground.cover_at(time - self.start));
+        gl.uniform1f(pass.uniform("u_detailDistance"), GROUND_DETAIL_DISTANCE);
+
+        gl.uniform1i(pass.uniform("u_fogMode"), fog.mode as i32);
+        gl.uniform3f(pass.uniform("u_fogParams"), fog.near, fog.far, fog.density);
+        let [r, g, b] = fog.color;
+        gl.uniform3f(pass.uniform("u_fogColor"), r, g, b);
+        Pass::draw(gl);
+
+        self.quad.unbind(gl);
+        gl.enable(GL::BLEND);
+    }
+
+    pub fn delete(&self, gl: &GL) {
+        self.quad.delete(gl);
+        gl.delete_program(Some(&self.pass.program));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cover_grows_until_fully_white() {
+        let ground = GroundConfig {
+            cover: 0.25,
+            accumulation: 10.0,
+            ..GroundConfig::default()
+        };
+        assert_eq!(ground.cover_at(0.0), 0.25);
+        assert_eq!(ground.cover_at(5.0), 0.75);
+        assert_eq!(ground.cover_at(7.5), 1.0);
+        assert_eq!(ground.cover_at(100.0), 1.0);
+    }
+
+    #[test]
+    fn zero_accumulation_keeps_cover() {
+        let ground = GroundConfig {
+            cover: 0.4,
+            accumulation: 0.0,
+            ..GroundConfig::default()
+        };
+        assert_eq!(ground.cover_at(0.0), 0.4);
+        assert_eq!(ground.cover_at(1000.0), 0.4);
+    }
+}
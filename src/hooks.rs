@@ -0,0 +1,227 @@
+//! User GLSL snippets spliced into the particle shaders
+
+use crate::config::get;
+use crate::shaders::{
+    ShaderFeatures, DEFAULT_DISPLACE, DEFAULT_SHADE, FRAGMENT_SHADER_SOURCE, HOOK_MARKER,
+    VERTEX_SHADER_SOURCE,
+};
+use wasm_bindgen::prelude::*;
+
+/// Optional replacements for the `displace` and `shade` shader functions.
+///
+/// `displace` runs in the vertex shader after all built-in motion:
+/// `vec3 displace(vec3 pos, float t)`. `shade` runs in the fragment shader
+/// before fog: `vec4 shade(vec4 color, vec2 uv)`. Each may read the
+/// uniforms of its shader, such as `u_time` in `displace`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShaderHooks {
+    pub displace: Option<String>,
+    pub shade: Option<String>,
+}
+
+impl ShaderHooks {
+    /// Reads `{ displace, shade }` strings.
+    pub fn from_js(value: &JsValue) -> Self {
+        let read = |key| get(value, key).and_then(|v| v.as_string());
+        Self {
+            displace: read("displace"),
+            shade: read("shade"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.displace.is_none() && self.shade.is_none()
+    }
+
+    /// Rejects snippets that could escape their function, such as
+    /// preprocessor directives redefining the rest of the shader.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, snippet) in [("displace", &self.displace), ("shade", &self.shade)] {
+            let Some(snippet) = snippet else {
+                continue;
+            };
+            if snippet.contains('#') {
+                return Err(format!("{name}: preprocessor directives are not allowed"));
+            }
+            // A closing brace before its opening one would end the host
+            // function, even if the totals match
+            let mut depth: i32 = 0;
+            for c in snippet.chars() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => continue,
+                }
+                if depth < 0 {
+                    break;
+                }
+            }
+            if depth != 0 {
+                return Err(format!("{name}: unbalanced braces"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn vertex_source(&self, features: ShaderFeatures) -> HookedSource {
+        HookedSource::splice(
+            &features.source(VERTEX_SHADER_SOURCE),
+            "displace",
+            self.displace.as_deref().unwrap_or(DEFAULT_DISPLACE),
+        )
+    }
+
+    pub fn fragment_source(&self, features: ShaderFeatures) -> HookedSource {
+        HookedSource::splice(
+            &features.source(FRAGMENT_SHADER_SOURCE),
+            "shade",
+            self.shade.as_deref().unwrap_or(DEFAULT_SHADE),
+        )
+    }
+}
+
+/// Shader source with a snippet in place of `HOOK_MARKER`, remembering
+/// where it landed to map compiler errors back to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HookedSource {
+    pub source: String,
+    pub hook: &'static str,
+    /// 1-based line of the first snippet line.
+    pub first_line: u32,
+    pub line_count: u32,
+}
+
+impl HookedSource {
+    pub fn splice(base: &str, hook: &'static str, snippet: &str) -> Self {
+        let offset = base.find(HOOK_MARKER).unwrap_or(base.len());
+        let first_line = base[..offset].matches('\n').count() as u32 + 1;
+        let snippet = snippet.trim_end();
+        Self {
+            source: base.replacen(HOOK_MARKER, snippet, 1),
+            hook,
+            first_line,
+            line_count: snippet.matches('\n').count() as u32 + 1,
+        }
+    }
+
+    /// Rewrites `ERROR: 0:<line>:` locations inside the snippet as
+    /// `ERROR: <hook>:<snippet line>:`. Other lines are left unchanged.
+    pub fn map_log(&self, log: &str) -> String {
+        log.lines()
+            .map(|line| self.map_line(line).unwrap_or_else(|| line.to_string()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn map_line(&self, line: &str) -> Option<String> {
+        let (severity, rest) = line.split_once(": 0:")?;
+        let (number, message) = rest.split_once(':')?;
+        let number: u32 = number.trim().parse().ok()?;
+        let local = number.checked_sub(self.first_line)?;
+        (local < self.line_count)
+            .then(|| format!("{severity}: {}:{}:{message}", self.hook, local + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DISPLACE: &str =
+        "vec3 displace(vec3 pos, float t) {\n    pos.y += t;\n    return pos;\n}\n";
+
+    fn hooks(displace: &str) -> ShaderHooks {
+        ShaderHooks {
+            displace: Some(displace.to_string()),
+            shade: None,
+        }
+    }
+
+    fn spliced(features: ShaderFeatures) -> HookedSource {
+        hooks(DISPLACE).vertex_source(features)
+    }
+
+    #[test]
+    fn splice_locates_snippet_after_defines() {
+        let mut features = ShaderFeatures::default();
+        let plain = spliced(features);
+        features.set(ShaderFeatures::FOG, true);
+        features.set(ShaderFeatures::GROUND, true);
+        let defined = spliced(features);
+
+        assert_eq!(defined.first_line, plain.first_line + 2);
+        for hooked in [plain, defined] {
+            assert_eq!(hooked.hook, "displace");
+            assert_eq!(hooked.line_count, 4);
+            let lines: Vec<&str> = hooked.source.lines().collect();
+            let first = hooked.first_line as usize - 1;
+            assert!(lines[first].trim_start().starts_with("vec3 displace("));
+            assert_eq!(lines[first + 3].trim(), "}");
+            assert!(!hooked.source.contains(HOOK_MARKER));
+        }
+    }
+
+    #[test]
+    fn map_log_rewrites_snippet_lines() {
+        let mut features = ShaderFeatures::default();
+        features.set(ShaderFeatures::TEXTURE, true);
+        let hooked = spliced(features);
+        let first = hooked.first_line;
+        let log = format!(
+            "ERROR: 0:{}: 'pos' : undeclared identifier\n\
+             WARNING: 0:{}: unused\n\
+             ERROR: 0:{}: before the snippet\n\
+             ERROR: 0:{}: after the snippet\n\
+             ERROR: 2 compilation errors.",
+            first + 1,
+            first + 3,
+            first - 1,
+            first + 4,
+        );
+        let mapped: Vec<String> = hooked.map_log(&log).lines().map(String::from).collect();
+        assert_eq!(
+            mapped[0],
+            "ERROR: displace:2: 'pos' : undeclared identifier"
+        );
+        assert_eq!(mapped[1], "WARNING: displace:4: unused");
+        assert_eq!(
+            mapped[2],
+            format!("ERROR: 0:{}: before the snippet", first - 1)
+        );
+        assert_eq!(
+            mapped[3],
+            format!("ERROR: 0:{}: after the snippet", first + 4)
+        );
+        assert_eq!(mapped[4], "ERROR: 2 compilation errors.");
+    }
+
+    #[test]
+    fn validate_accepts_nested_blocks() {
+        assert_eq!(hooks(DISPLACE).validate(), Ok(()));
+        let nested =
+            "vec3 displace(vec3 pos, float t) { if (t > 1.0) { pos.x = 0.0; } return pos; }";
+        assert_eq!(hooks(nested).validate(), Ok(()));
+        assert_eq!(ShaderHooks::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_escaping_snippets() {
+        for snippet in [
+            "vec3 displace(vec3 pos, float t) { return pos;",
+            "vec3 displace(vec3 pos, float t) { return pos; }}",
+            "} void f() {",
+            "vec3 displace(vec3 pos, float t) { return pos; } } void g() {",
+            "#define X 1",
+        ] {
+            assert!(hooks(snippet).validate().is_err(), "{snippet}");
+        }
+        let shade = ShaderHooks {
+            displace: None,
+            shade: Some("} {".to_string()),
+        };
+        assert_eq!(
+            shade.validate(),
+            Err("shade: unbalanced braces".to_string())
+        );
+    }
+}
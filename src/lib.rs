@@ -3,14 +3,42 @@
 use wasm_bindgen::prelude::*;
 use web_sys::console;
 
+pub mod background;
+pub mod camera;
 pub mod config;
 pub mod constants;
+pub mod emitter;
+pub mod formation;
+pub mod globe;
+pub mod ground;
+pub mod hooks;
+pub mod mask;
+pub mod palette;
+pub mod postprocess;
+pub mod precipitation;
+pub mod procedural;
 pub mod renderer;
 pub mod shaders;
+pub mod texture;
+pub mod theme;
 
-pub use config::{SnowConfig, WindState};
+pub use background::{Background, BackgroundFit, BackgroundLayer};
+pub use camera::{Camera, CameraConfig, OrbitMode};
+pub use config::{FogConfig, FogMode, SnowConfig, WindState};
 pub use constants::*;
+pub use emitter::{Emitter, EmitterConfig, EmitterShape, SpawnSpace};
+pub use formation::{Formation, FormationOptions, Silhouette};
+pub use globe::{GlassLayer, GlobeConfig, GlobeState};
+pub use ground::{GroundConfig, GroundLayer};
+pub use hooks::{HookedSource, ShaderHooks};
+pub use mask::{MaskConfig, MaskRegion, OcclusionMask};
+pub use palette::{Palette, PaletteSampling};
+pub use postprocess::{PostConfig, PostProcessor};
+pub use precipitation::{Precipitation, PrecipitationPreset};
+pub use procedural::{generate_atlas, generate_crystal, CrystalAtlas, ProceduralConfig};
 pub use renderer::{RenderResult, SnowfallShader};
+pub use texture::{AtlasEntry, AtlasLayout, SpriteSheet, TextureOptions, TextureSource};
+pub use theme::{BlendMode, Shape, Theme, ThemePreset};
 
 #[wasm_bindgen(start)]
 pub fn main() {
@@ -27,6 +55,12 @@ pub fn is_runtime_configurable() -> bool {
     RUNTIME_CONFIGURABLE
 }
 
+/// Names of the built-in particle themes.
+#[wasm_bindgen]
+pub fn themes() -> Vec<String> {
+    Theme::ALL.iter().map(|t| t.name().into()).collect()
+}
+
 #[wasm_bindgen]
 pub fn version() -> String {
     env!("CARGO_PKG_VERSION").into()
@@ -0,0 +1,239 @@
+//! Occlusion masks keeping flakes off text and other page content
+
+use crate::config::{get, get_f32};
+use crate::constants::*;
+use crate::texture::{create_canvas, TextureSource};
+use wasm_bindgen::prelude::*;
+use web_sys::{
+    CanvasRenderingContext2d, Element, HtmlCanvasElement, Path2d, WebGlRenderingContext as GL,
+    WebGlTexture,
+};
+
+/// Screen area where flakes are hidden.
+#[derive(Clone, Debug)]
+pub enum MaskRegion {
+    /// Measured on every mask update, so it follows layout and scrolling.
+    Element(Element),
+    /// CSS pixels relative to the canvas.
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    /// SVG path data in CSS pixels relative to the canvas.
+    Path(String),
+}
+
+impl MaskRegion {
+    /// Accepts an element, SVG path data or `{ x, y, width, height }`, such
+    /// as a `DOMRect`.
+    pub fn from_js(value: &JsValue) -> Option<Self> {
+        if let Some(element) = value.dyn_ref::<Element>() {
+            return Some(Self::Element(element.clone()));
+        }
+        if let Some(path) = value.as_string() {
+            return Some(Self::Path(path));
+        }
+        Some(Self::Rect {
+            x: get_f32(value, "x")?,
+            y: get_f32(value, "y")?,
+            width: get_f32(value, "width")?,
+            height: get_f32(value, "height")?,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MaskConfig {
+    pub regions: Vec<MaskRegion>,
+    /// Image stretched over the canvas; its alpha hides flakes.
+    pub image: Option<TextureSource>,
+    /// Soft edge width in CSS pixels.
+    pub feather: f32,
+    /// How much masked flakes fade, 1 hides them completely.
+    pub opacity: f32,
+}
+
+impl Default for MaskConfig {
+    fn default() -> Self {
+        Self {
+            regions: Vec::new(),
+            image: None,
+            feather: DEFAULT_MASK_FEATHER,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl MaskConfig {
+    /// Accepts a list of regions, a mask image or
+    /// `{ regions, image, feather, opacity }`. Returns `None` if nothing
+    /// is masked.
+    pub fn from_js(value: &JsValue) -> Option<Self> {
+        let mut config = Self::default();
+        if js_sys::Array::is_array(value) {
+            config.regions = parse_regions(value);
+        } else if let Some(image) = TextureSource::from_js(value) {
+            config.image = Some(image);
+        } else if value.is_object() {
+            if let Some(regions) = get(value, "regions") {
+                config.regions = parse_regions(&regions);
+            }
+            config.image = get(value, "image").and_then(|v| TextureSource::from_js(&v));
+            if let Some(v) = get_f32(value, "feather") {
+                config.feather = v.max(0.0);
+            }
+            if let Some(v) = get_f32(value, "opacity") {
+                config.opacity = v.clamp(0.0, 1.0);
+            }
+        }
+        (!config.regions.is_empty() || config.image.is_some()).then_some(config)
+    }
+
+    /// Whether the mask depends on the page layout and scroll position.
+    pub fn tracks_elements(&self) -> bool {
+        self.regions
+            .iter()
+            .any(|r| matches!(r, MaskRegion::Element(_)))
+    }
+}
+
+fn parse_regions(value: &JsValue) -> Vec<MaskRegion> {
+    if !js_sys::Array::is_array(value) {
+        return MaskRegion::from_js(value).into_iter().collect();
+    }
+    js_sys::Array::from(value)
+        .iter()
+        .filter_map(|v| MaskRegion::from_js(&v))
+        .collect()
+}
+
+/// Mask rasterized at reduced resolution and uploaded to
+/// `MASK_TEXTURE_UNIT`.
+pub struct OcclusionMask {
+    canvas: HtmlCanvasElement,
+    ctx: CanvasRenderingContext2d,
+    texture: WebGlTexture,
+    /// Document scroll offset at the last update.
+    scroll: (f64, f64),
+    /// Whether the mask image had loaded at the last update.
+    complete: bool,
+}
+
+impl OcclusionMask {
+    pub fn new(gl: &GL) -> Result<Self, JsValue> {
+        let (canvas, ctx) = create_canvas(1, 1)?;
+        let texture = gl.create_texture().ok_or("Failed to create texture")?;
+        Ok(Self {
+            canvas,
+            ctx,
+            texture,
+            scroll: (0.0, 0.0),
+            complete: false,
+        })
+    }
+
+    /// Whether the page scrolled under element regions or the mask image
+    /// finished loading since the last update.
+    pub fn is_stale(&self, config: &MaskConfig) -> bool {
+        if !self.complete && config.image.as_ref().is_some_and(|i| i.is_ready()) {
+            return true;
+        }
+        config.tracks_elements() && scroll_offset() != self.scroll
+    }
+
+    /// Redraws the mask for `target`, the canvas the flakes are drawn on.
+    pub fn update(
+        &mut self,
+        gl: &GL,
+        config: &MaskConfig,
+        target: &HtmlCanvasElement,
+    ) -> Result<(), JsValue> {
+        let (css_width, css_height) = (target.client_width(), target.client_height());
+        let scale = MASK_SCALE as f64;
+        let width = ((css_width as f64 * scale).ceil() as u32).max(1);
+        let height = ((css_height as f64 * scale).ceil() as u32).max(1);
+        if (self.canvas.width(), self.canvas.height()) != (width, height) {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+        }
+
+        let ctx = &self.ctx;
+        ctx.reset_transform()?;
+        ctx.clear_rect(0.0, 0.0, width as f64, height as f64);
+        ctx.set_transform(scale, 0.0, 0.0, scale, 0.0, 0.0)?;
+        let blur = config.feather as f64 * scale;
+        ctx.set_filter(&if blur > 0.0 {
+            format!("blur({blur}px)")
+        } else {
+            "none".to_string()
+        });
+        ctx.set_global_alpha(config.opacity as f64);
+        ctx.set_fill_style_str("#000");
+
+        if let Some(image) = config.image.as_ref().filter(|i| i.is_ready()) {
+            image.draw(ctx, 0.0, 0.0, css_width as f64, css_height as f64)?;
+        }
+        let origin = target.get_bounding_client_rect();
+        for region in &config.regions {
+            match region {
+                MaskRegion::Element(element) => {
+                    let rect = element.get_bounding_client_rect();
+                    ctx.fill_rect(
+                        rect.x() - origin.x(),
+                        rect.y() - origin.y(),
+                        rect.width(),
+                        rect.height(),
+                    );
+                }
+                MaskRegion::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => ctx.fill_rect(*x as f64, *y as f64, *width as f64, *height as f64),
+                MaskRegion::Path(path) => {
+                    ctx.fill_with_path_2d(&Path2d::new_with_path_string(path)?)
+                }
+            }
+        }
+
+        gl.active_texture(GL::TEXTURE0 + MASK_TEXTURE_UNIT);
+        gl.bind_texture(GL::TEXTURE_2D, Some(&self.texture));
+        gl.pixel_storei(GL::UNPACK_FLIP_Y_WEBGL, 0);
+        gl.pixel_storei(GL::UNPACK_PREMULTIPLY_ALPHA_WEBGL, 0);
+        let uploaded = gl.tex_image_2d_with_u32_and_u32_and_canvas(
+            GL::TEXTURE_2D,
+            0,
+            GL::RGBA as i32,
+            GL::RGBA,
+            GL::UNSIGNED_BYTE,
+            &self.canvas,
+        );
+        for (param, value) in [
+            (GL::TEXTURE_MIN_FILTER, GL::LINEAR),
+            (GL::TEXTURE_MAG_FILTER, GL::LINEAR),
+            (GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE),
+            (GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE),
+        ] {
+            gl.tex_parameteri(GL::TEXTURE_2D, param, value as i32);
+        }
+        gl.active_texture(GL::TEXTURE0);
+        uploaded?;
+
+        self.scroll = scroll_offset();
+        self.complete = config.image.iter().all(|i| i.is_ready());
+        Ok(())
+    }
+
+    pub fn delete(&self, gl: &GL) {
+        gl.delete_texture(Some(&self.texture));
+    }
+}
+
+fn scroll_offset() -> (f64, f64) {
+    web_sys::window()
+        .map(|w| (w.scroll_x().unwrap_or(0.0), w.scroll_y().unwrap_or(0.0)))
+        .unwrap_or_default()
+}
@@ -0,0 +1,232 @@
+//! Per-particle color palettes
+
+use crate::config::{get, get_floats};
+use wasm_bindgen::prelude::*;
+
+/// How a palette assigns a color to each particle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteSampling {
+    /// Picks one of the colors at random, proportionally to its weight.
+    Weighted,
+    /// Gradient from the farthest (first color) to the nearest flakes.
+    Depth,
+    /// Gradient from the smallest (first color) to the largest flakes.
+    Size,
+    /// Gradient sampled at a random position.
+    Random,
+}
+
+impl PaletteSampling {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "depth" => Some(Self::Depth),
+            "size" => Some(Self::Size),
+            "random" => Some(Self::Random),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub colors: Vec<[f32; 3]>,
+    /// Relative weights for `Weighted` sampling, one per color.
+    pub weights: Vec<f32>,
+    pub sampling: PaletteSampling,
+}
+
+impl Palette {
+    /// Equally weighted colors.
+    pub fn uniform(colors: &[[f32; 3]]) -> Self {
+        Self {
+            colors: colors.to_vec(),
+            weights: vec![1.0; colors.len()],
+            sampling: PaletteSampling::Weighted,
+        }
+    }
+
+    /// Accepts an array of `[r, g, b]` colors, `{ colors, weights }` or
+    /// `{ gradient, by }` where `by` is `"depth"`, `"size"` or `"random"`.
+    pub fn from_js(value: &JsValue) -> Option<Self> {
+        if js_sys::Array::is_array(value) {
+            return Some(Self::uniform(&parse_colors(value)?));
+        }
+        if !value.is_object() {
+            return None;
+        }
+
+        if let Some(gradient) = get(value, "gradient") {
+            let sampling = get(value, "by")
+                .and_then(|v| v.as_string())
+                .and_then(|s| PaletteSampling::parse(&s))
+                .unwrap_or(PaletteSampling::Depth);
+            let colors = parse_colors(&gradient)?;
+            return Some(Self {
+                weights: vec![1.0; colors.len()],
+                colors,
+                sampling,
+            });
+        }
+
+        let colors = parse_colors(&get(value, "colors")?)?;
+        let mut weights = get_floats(value, "weights").unwrap_or_default();
+        weights.resize(colors.len(), 1.0);
+        Some(Self {
+            colors,
+            weights,
+            sampling: PaletteSampling::Weighted,
+        })
+    }
+
+    /// Color for a particle. `random` is uniform in [0, 1); `nearness` and
+    /// `size` are the particle's depth and size normalized to [0, 1].
+    pub fn sample(&self, random: f32, nearness: f32, size: f32) -> [f32; 3] {
+        let t = match self.sampling {
+            PaletteSampling::Weighted => return self.pick(random),
+            PaletteSampling::Depth => nearness,
+            PaletteSampling::Size => size,
+            PaletteSampling::Random => random,
+        };
+        self.gradient(t)
+    }
+
+    fn pick(&self, random: f32) -> [f32; 3] {
+        self.colors[weighted_index(&self.weights, random).min(self.colors.len() - 1)]
+    }
+
+    fn gradient(&self, t: f32) -> [f32; 3] {
+        let last = self.colors.len() - 1;
+        let scaled = t.clamp(0.0, 1.0) * last as f32;
+        let i = (scaled as usize).min(last.saturating_sub(1));
+        let (a, b) = (self.colors[i], self.colors[(i + 1).min(last)]);
+        let f = scaled - i as f32;
+        [
+            a[0] + (b[0] - a[0]) * f,
+            a[1] + (b[1] - a[1]) * f,
+            a[2] + (b[2] - a[2]) * f,
+        ]
+    }
+}
+
+/// Index picked with probability proportional to its weight, for `random`
+/// uniform in [0, 1). Negative weights count as zero.
+pub fn weighted_index(weights: &[f32], random: f32) -> usize {
+    let total: f32 = weights.iter().map(|w| w.max(0.0)).sum();
+    if total <= 0.0 {
+        return 0;
+    }
+    let mut target = random * total;
+    for (i, weight) in weights.iter().enumerate() {
+        target -= weight.max(0.0);
+        if target < 0.0 {
+            return i;
+        }
+    }
+    weights.len() - 1
+}
+
+/// Parses an array of `[r, g, b]` arrays. Returns `None` if it has no valid color.
+fn parse_colors(value: &JsValue) -> Option<Vec<[f32; 3]>> {
+    if !js_sys::Array::is_array(value) {
+        return None;
+    }
+    let colors: Vec<[f32; 3]> = js_sys::Array::from(value)
+        .iter()
+        .filter(js_sys::Array::is_array)
+        .filter_map(|c| {
+            let rgb: Vec<f32> = js_sys::Array::from(&c)
+                .iter()
+                .filter_map(|x| x.as_f64().map(|n| n as f32))
+                .collect();
+            match rgb.as_slice() {
+                [r, g, b, ..] => Some([*r, *g, *b]),
+                _ => None,
+            }
+        })
+        .collect();
+    (!colors.is_empty()).then_some(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [f32; 3] = [1.0, 0.0, 0.0];
+    const GREEN: [f32; 3] = [0.0, 1.0, 0.0];
+    const BLUE: [f32; 3] = [0.0, 0.0, 1.0];
+
+    fn gradient(sampling: PaletteSampling) -> Palette {
+        Palette {
+            sampling,
+            ..Palette::uniform(&[RED, GREEN, BLUE])
+        }
+    }
+
+    #[test]
+    fn weighted_index_is_proportional() {
+        let weights = [1.0, 3.0];
+        let samples = 1000;
+        let picked = (0..samples)
+            .filter(|&i| weighted_index(&weights, i as f32 / samples as f32) == 1)
+            .count();
+        assert_eq!(picked, 750);
+        assert_eq!(weighted_index(&weights, 0.0), 0);
+        assert_eq!(weighted_index(&weights, 0.999), 1);
+    }
+
+    #[test]
+    fn weighted_index_skips_zero_and_negative_weights() {
+        let weights = [0.0, -2.0, 1.0, 0.0];
+        for random in [0.0, 0.3, 0.999] {
+            assert_eq!(weighted_index(&weights, random), 2);
+        }
+        assert_eq!(weighted_index(&[0.0, 0.0], 0.5), 0);
+        assert_eq!(weighted_index(&[-1.0], 0.5), 0);
+        assert_eq!(weighted_index(&[], 0.5), 0);
+    }
+
+    #[test]
+    fn weighted_sampling_picks_colors() {
+        let palette = Palette {
+            weights: vec![0.0, 1.0, 0.0],
+            ..Palette::uniform(&[RED, GREEN, BLUE])
+        };
+        assert_eq!(palette.sample(0.0, 0.0, 0.0), GREEN);
+        assert_eq!(palette.sample(0.99, 1.0, 1.0), GREEN);
+    }
+
+    #[test]
+    fn single_color_gradient() {
+        let palette = Palette {
+            sampling: PaletteSampling::Depth,
+            ..Palette::uniform(&[GREEN])
+        };
+        for t in [0.0, 0.5, 1.0] {
+            assert_eq!(palette.sample(0.5, t, t), GREEN);
+        }
+    }
+
+    #[test]
+    fn gradient_endpoints_and_midpoints() {
+        let depth = gradient(PaletteSampling::Depth);
+        assert_eq!(depth.sample(0.5, 0.0, 0.5), RED);
+        assert_eq!(depth.sample(0.5, 0.5, 0.5), GREEN);
+        assert_eq!(depth.sample(0.5, 1.0, 0.5), BLUE);
+        assert_eq!(depth.sample(0.5, 0.25, 0.5), [0.5, 0.5, 0.0]);
+
+        let size = gradient(PaletteSampling::Size);
+        assert_eq!(size.sample(0.5, 0.5, 0.0), RED);
+        assert_eq!(size.sample(0.5, 0.5, 1.0), BLUE);
+
+        let random = gradient(PaletteSampling::Random);
+        assert_eq!(random.sample(0.0, 0.5, 0.5), RED);
+        assert_eq!(random.sample(1.0, 0.5, 0.5), BLUE);
+    }
+
+    #[test]
+    fn gradient_clamps_out_of_range() {
+        let depth = gradient(PaletteSampling::Depth);
+        assert_eq!(depth.sample(0.5, -1.0, 0.5), RED);
+        assert_eq!(depth.sample(0.5, 2.0, 0.5), BLUE);
+    }
+}
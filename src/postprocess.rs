@@ -0,0 +1,405 @@
+//! Framebuffer post-processing: bloom and vignette
+
+use std::collections::HashMap;
+
+use crate::config::get_f32;
+use crate::constants::*;
+use crate::shaders::{
+    BLUR_SHADER_SOURCE, BRIGHT_PASS_SHADER_SOURCE, COMPOSITE_SHADER_SOURCE,
+    POST_VERTEX_SHADER_SOURCE,
+};
+use wasm_bindgen::prelude::*;
+use web_sys::{
+    WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlRenderingContext as GL, WebGlShader,
+    WebGlTexture, WebGlUniformLocation,
+};
+
+/// Last of the 8 attributes WebGL guarantees, clear of the particle ones.
+const QUAD_LOCATION: u32 = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PostConfig {
+    /// Strength of the light bleeding from bright flakes, 0 disables bloom.
+    pub bloom: f32,
+    /// Brightness (0-1) above which pixels bloom.
+    pub threshold: f32,
+    /// Blur spread in texels of the downsampled buffers.
+    pub radius: f32,
+    /// Darkening of the screen edges, 0-1.
+    pub vignette: f32,
+    /// Resolution of the bloom buffers relative to the canvas.
+    pub scale: f32,
+}
+
+impl Default for PostConfig {
+    fn default() -> Self {
+        Self {
+            bloom: 0.0,
+            threshold: DEFAULT_BLOOM_THRESHOLD,
+            radius: 1.0,
+            vignette: 0.0,
+            scale: DEFAULT_POST_SCALE,
+        }
+    }
+}
+
+impl PostConfig {
+    /// Reads `{ bloom, threshold, radius, vignette, scale }`.
+    pub fn from_js(value: &JsValue) -> Self {
+        let mut config = Self::default();
+        if let Some(v) = get_f32(value, "bloom") {
+            config.bloom = v.max(0.0);
+        }
+        if let Some(v) = get_f32(value, "threshold") {
+            config.threshold = v.clamp(0.0, 1.0);
+        }
+        if let Some(v) = get_f32(value, "radius") {
+            config.radius = v.max(0.0);
+        }
+        if let Some(v) = get_f32(value, "vignette") {
+            config.vignette = v.clamp(0.0, 1.0);
+        }
+        if let Some(v) = get_f32(value, "scale") {
+            config.scale = v.clamp(POST_MIN_SCALE, 1.0);
+        }
+        config
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.bloom > 0.0 || self.vignette > 0.0
+    }
+}
+
+/// Texture with a framebuffer rendering into it.
+struct Target {
+    framebuffer: WebGlFramebuffer,
+    texture: WebGlTexture,
+    width: i32,
+    height: i32,
+}
+
+impl Target {
+    fn new(gl: &GL, width: u32, height: u32) -> Result<Self, JsValue> {
+        let (width, height) = (width.max(1) as i32, height.max(1) as i32);
+        let texture = gl.create_texture().ok_or("Failed to create texture")?;
+        gl.bind_texture(GL::TEXTURE_2D, Some(&texture));
+        gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            GL::TEXTURE_2D,
+            0,
+            GL::RGBA as i32,
+            width,
+            height,
+            0,
+            GL::RGBA,
+            GL::UNSIGNED_BYTE,
+            None,
+        )?;
+        for (param, value) in [
+            (GL::TEXTURE_MIN_FILTER, GL::LINEAR),
+            (GL::TEXTURE_MAG_FILTER, GL::LINEAR),
+            (GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE),
+            (GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE),
+        ] {
+            gl.tex_parameteri(GL::TEXTURE_2D, param, value as i32);
+        }
+
+        let framebuffer = gl
+            .create_framebuffer()
+            .ok_or("Failed to create framebuffer")?;
+        gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&framebuffer));
+        gl.framebuffer_texture_2d(
+            GL::FRAMEBUFFER,
+            GL::COLOR_ATTACHMENT0,
+            GL::TEXTURE_2D,
+            Some(&texture),
+            0,
+        );
+        let status = gl.check_framebuffer_status(GL::FRAMEBUFFER);
+        gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+        if status != GL::FRAMEBUFFER_COMPLETE {
+            return Err(format!("Framebuffer incomplete: {status:#x}").into());
+        }
+
+        Ok(Self {
+            framebuffer,
+            texture,
+            width,
+            height,
+        })
+    }
+
+    fn delete(&self, gl: &GL) {
+        gl.delete_framebuffer(Some(&self.framebuffer));
+        gl.delete_texture(Some(&self.texture));
+    }
+}
+
+/// Two triangles covering the viewport, fed to `a_quad`.
+pub(crate) struct Quad(WebGlBuffer);
+
+impl Quad {
+    pub(crate) fn new(gl: &GL) -> Result<Self, JsValue> {
+        let buffer = gl.create_buffer().ok_or("Failed to create buffer")?;
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&buffer));
+        let corners: [f32; 8] = [-1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0];
+        unsafe {
+            let array = js_sys::Float32Array::view(&corners);
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &array, GL::STATIC_DRAW);
+        }
+        Ok(Self(buffer))
+    }
+
+    /// Enables the quad attribute. Pair with `unbind` before drawing
+    /// particles again.
+    pub(crate) fn bind(&self, gl: &GL) {
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.0));
+        gl.enable_vertex_attrib_array(QUAD_LOCATION);
+        gl.vertex_attrib_pointer_with_i32(QUAD_LOCATION, 2, GL::FLOAT, false, 0, 0);
+    }
+
+    pub(crate) fn unbind(&self, gl: &GL) {
+        gl.disable_vertex_attrib_array(QUAD_LOCATION);
+    }
+
+    pub(crate) fn delete(&self, gl: &GL) {
+        gl.delete_buffer(Some(&self.0));
+    }
+}
+
+/// Fullscreen pass with its uniform locations.
+pub(crate) struct Pass {
+    pub(crate) program: WebGlProgram,
+    uniforms: HashMap<&'static str, WebGlUniformLocation>,
+}
+
+impl Pass {
+    /// Links `fragment` with the shared quad vertex shader.
+    pub(crate) fn new(gl: &GL, fragment: &str, names: &[&'static str]) -> Result<Self, JsValue> {
+        Self::with_vertex(gl, POST_VERTEX_SHADER_SOURCE, fragment, names)
+    }
+
+    /// Links `fragment` with a vertex shader reading `a_quad`.
+    pub(crate) fn with_vertex(
+        gl: &GL,
+        vertex: &str,
+        fragment: &str,
+        names: &[&'static str],
+    ) -> Result<Self, JsValue> {
+        let vs = compile(gl, GL::VERTEX_SHADER, vertex)?;
+        let fs = compile(gl, GL::FRAGMENT_SHADER, fragment)?;
+
+        let program = gl.create_program().ok_or("Failed to create program")?;
+        gl.attach_shader(&program, &vs);
+        gl.attach_shader(&program, &fs);
+        gl.bind_attrib_location(&program, QUAD_LOCATION, "a_quad");
+        gl.link_program(&program);
+        gl.delete_shader(Some(&vs));
+        gl.delete_shader(Some(&fs));
+
+        if !gl
+            .get_program_parameter(&program, GL::LINK_STATUS)
+            .as_bool()
+            .unwrap_or(false)
+        {
+            let info = gl.get_program_info_log(&program).unwrap_or_default();
+            return Err(format!("Post-processing link error: {info}").into());
+        }
+
+        let uniforms = names
+            .iter()
+            .filter_map(|&name| Some((name, gl.get_uniform_location(&program, name)?)))
+            .collect();
+        Ok(Self { program, uniforms })
+    }
+
+    pub(crate) fn uniform(&self, name: &str) -> Option<&WebGlUniformLocation> {
+        self.uniforms.get(name)
+    }
+
+    /// Draws the quad with `source` on texture unit `POST_TEXTURE_UNIT`
+    /// into `target`, or the canvas when `None`. Expects the program to be
+    /// in use with its uniforms set.
+    fn run(&self, gl: &GL, source: &Target, target: Option<&Target>, viewport: (i32, i32)) {
+        gl.bind_framebuffer(GL::FRAMEBUFFER, target.map(|t| &t.framebuffer));
+        let (width, height) = target.map_or(viewport, |t| (t.width, t.height));
+        gl.viewport(0, 0, width, height);
+
+        gl.active_texture(GL::TEXTURE0 + POST_TEXTURE_UNIT);
+        gl.bind_texture(GL::TEXTURE_2D, Some(&source.texture));
+        Self::draw(gl);
+    }
+
+    /// Draws the bound quad with the current framebuffer and viewport.
+    pub(crate) fn draw(gl: &GL) {
+        gl.draw_arrays(GL::TRIANGLE_STRIP, 0, 4);
+    }
+}
+
+fn compile(gl: &GL, kind: u32, source: &str) -> Result<WebGlShader, JsValue> {
+    let shader = gl.create_shader(kind).ok_or("Failed to create shader")?;
+    gl.shader_source(&shader, source);
+    gl.compile_shader(&shader);
+    if !gl
+        .get_shader_parameter(&shader, GL::COMPILE_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        let info = gl.get_shader_info_log(&shader).unwrap_or_default();
+        gl.delete_shader(Some(&shader));
+        return Err(format!("Post-processing compilation error: {info}").into());
+    }
+    Ok(shader)
+}
+
+/// Particles are drawn into `scene`; `finish` extracts and blurs the bright
+/// parts at reduced resolution and composites everything onto the canvas.
+pub struct PostProcessor {
+    quad: Quad,
+    bright: Pass,
+    blur: Pass,
+    composite: Pass,
+    scene: Target,
+    /// Downsampled buffers the blur ping-pongs between.
+    bloom: [Target; 2],
+    width: u32,
+    height: u32,
+    scale: f32,
+}
+
+impl PostProcessor {
+    pub fn new(gl: &GL, width: u32, height: u32, scale: f32) -> Result<Self, JsValue> {
+        let quad = Quad::new(gl)?;
+        let bright = Pass::new(gl, BRIGHT_PASS_SHADER_SOURCE, &["u_source", "u_threshold"])?;
+        let blur = Pass::new(gl, BLUR_SHADER_SOURCE, &["u_source", "u_step"])?;
+        let composite = Pass::new(
+            gl,
+            COMPOSITE_SHADER_SOURCE,
+            &[
+                "u_scene",
+                "u_bloom",
+                "u_bloomIntensity",
+                "u_vignette",
+                "u_aspect",
+            ],
+        )?;
+        gl.active_texture(GL::TEXTURE0 + POST_TEXTURE_UNIT);
+        let (scene, bloom) = Self::targets(gl, width, height, scale)?;
+        gl.active_texture(GL::TEXTURE0);
+
+        Ok(Self {
+            quad,
+            bright,
+            blur,
+            composite,
+            scene,
+            bloom,
+            width,
+            height,
+            scale,
+        })
+    }
+
+    fn targets(
+        gl: &GL,
+        width: u32,
+        height: u32,
+        scale: f32,
+    ) -> Result<(Target, [Target; 2]), JsValue> {
+        let (bloom_width, bloom_height) = (
+            (width as f32 * scale) as u32,
+            (height as f32 * scale) as u32,
+        );
+        Ok((
+            Target::new(gl, width, height)?,
+            [
+                Target::new(gl, bloom_width, bloom_height)?,
+                Target::new(gl, bloom_width, bloom_height)?,
+            ],
+        ))
+    }
+
+    /// Recreates the buffers when the canvas size or the scale changed.
+    pub fn resize(&mut self, gl: &GL, width: u32, height: u32, scale: f32) -> Result<(), JsValue> {
+        if (width, height, scale) == (self.width, self.height, self.scale) {
+            return Ok(());
+        }
+        gl.active_texture(GL::TEXTURE0 + POST_TEXTURE_UNIT);
+        let targets = Self::targets(gl, width, height, scale);
+        gl.active_texture(GL::TEXTURE0);
+        let (scene, bloom) = targets?;
+
+        self.delete_targets(gl);
+        (self.scene, self.bloom) = (scene, bloom);
+        (self.width, self.height, self.scale) = (width, height, scale);
+        Ok(())
+    }
+
+    /// Redirects drawing into the scene buffer.
+    pub fn begin(&self, gl: &GL) {
+        gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&self.scene.framebuffer));
+        gl.viewport(0, 0, self.scene.width, self.scene.height);
+    }
+
+    /// Runs the passes and draws the result to the canvas. Leaves blending
+    /// on, the canvas bound and texture unit 0 active, but not the
+    /// particle program.
+    pub fn finish(&self, gl: &GL, config: &PostConfig) {
+        gl.disable(GL::BLEND);
+        self.quad.bind(gl);
+
+        let unit = POST_TEXTURE_UNIT as i32;
+        let [a, b] = &self.bloom;
+        if config.bloom > 0.0 {
+            gl.use_program(Some(&self.bright.program));
+            gl.uniform1i(self.bright.uniform("u_source"), unit);
+            gl.uniform1f(self.bright.uniform("u_threshold"), config.threshold);
+            self.bright.run(gl, &self.scene, Some(a), (0, 0));
+
+            gl.use_program(Some(&self.blur.program));
+            gl.uniform1i(self.blur.uniform("u_source"), unit);
+            let (dx, dy) = (
+                config.radius / a.width as f32,
+                config.radius / a.height as f32,
+            );
+            for _ in 0..BLOOM_BLUR_PASSES {
+                gl.uniform2f(self.blur.uniform("u_step"), dx, 0.0);
+                self.blur.run(gl, a, Some(b), (0, 0));
+                gl.uniform2f(self.blur.uniform("u_step"), 0.0, dy);
+                self.blur.run(gl, b, Some(a), (0, 0));
+            }
+        }
+
+        let composite = &self.composite;
+        gl.use_program(Some(&composite.program));
+        gl.active_texture(GL::TEXTURE0 + POST_TEXTURE_UNIT + 1);
+        gl.bind_texture(GL::TEXTURE_2D, Some(&a.texture));
+        gl.uniform1i(composite.uniform("u_scene"), unit);
+        gl.uniform1i(composite.uniform("u_bloom"), unit + 1);
+        gl.uniform1f(composite.uniform("u_bloomIntensity"), config.bloom);
+        gl.uniform1f(composite.uniform("u_vignette"), config.vignette);
+        gl.uniform1f(
+            composite.uniform("u_aspect"),
+            self.scene.width as f32 / self.scene.height as f32,
+        );
+        composite.run(gl, &self.scene, None, (self.scene.width, self.scene.height));
+
+        self.quad.unbind(gl);
+        gl.active_texture(GL::TEXTURE0);
+        gl.enable(GL::BLEND);
+    }
+
+    fn delete_targets(&self, gl: &GL) {
+        self.scene.delete(gl);
+        for target in &self.bloom {
+            target.delete(gl);
+        }
+    }
+
+    pub fn delete(&self, gl: &GL) {
+        self.delete_targets(gl);
+        self.quad.delete(gl);
+        for pass in [&self.bright, &self.blur, &self.composite] {
+            gl.delete_program(Some(&pass.program));
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Precipitation types and their parameter presets
+
+use crate::constants::*;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precipitation {
+    Snow,
+    /// Fast, thin drops rendered as streaks.
+    Rain,
+    /// Half drops, half small ice pellets.
+    Sleet,
+    /// Heavy pellets that bounce when they land.
+    Hail,
+}
+
+impl Precipitation {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "snow" => Some(Self::Snow),
+            "rain" => Some(Self::Rain),
+            "sleet" => Some(Self::Sleet),
+            "hail" => Some(Self::Hail),
+            _ => None,
+        }
+    }
+
+    pub fn preset(self) -> PrecipitationPreset {
+        match self {
+            Self::Snow => PrecipitationPreset::SNOW,
+            Self::Rain => PrecipitationPreset::RAIN,
+            Self::Sleet => PrecipitationPreset::SLEET,
+            Self::Hail => PrecipitationPreset::HAIL,
+        }
+    }
+}
+
+/// Defaults and per-particle distributions of a precipitation type.
+///
+/// The first group seeds the matching `SnowConfig` fields and can still be
+/// overridden; the rest shapes the random distributions in `setup_buffers`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrecipitationPreset {
+    pub gravity: f32,
+    pub speed_x: f32,
+    pub speed_y: f32,
+    pub min_size: f32,
+    pub min_alpha: f32,
+    pub rotation_speed: f32,
+    pub streak_length: f32,
+
+    /// Random extra wind response added to `speed_x`.
+    pub speed_x_jitter: f32,
+    /// Random extra fall speed added to `speed_y`.
+    pub speed_y_jitter: f32,
+    /// Random extra size added to `min_size`.
+    pub size_range: f32,
+    /// Side-to-side swing amplitude multiplier.
+    pub sway: f32,
+    /// Share of particles drawn with streaks; the rest stay round.
+    pub streak_share: f32,
+    /// Bounce height in world units when hitting the floor, 0 to disable.
+    pub bounce: f32,
+}
+
+impl PrecipitationPreset {
+    pub const SNOW: Self = Self {
+        gravity: DEFAULT_GRAVITY,
+        speed_x: DEFAULT_SPEED_X,
+        speed_y: DEFAULT_SPEED_Y,
+        min_size: DEFAULT_MIN_SIZE,
+        min_alpha: DEFAULT_MIN_ALPHA,
+        rotation_speed: DEFAULT_ROTATION_SPEED,
+        streak_length: DEFAULT_STREAK_LENGTH,
+        speed_x_jitter: 0.4,
+        speed_y_jitter: 0.5,
+        size_range: 4.5,
+        sway: 1.0,
+        streak_share: 1.0,
+        bounce: 0.0,
+    };
+
+    pub const RAIN: Self = Self {
+        gravity: 45.0,
+        speed_x: 0.6,
+        speed_y: 1.0,
+        min_size: 1.2,
+        min_alpha: 0.25,
+        rotation_speed: 0.0,
+        streak_length: 0.06,
+        speed_x_jitter: 0.2,
+        speed_y_jitter: 0.4,
+        size_range: 1.0,
+        sway: 0.0,
+        streak_share: 1.0,
+        bounce: 0.0,
+    };
+
+    pub const SLEET: Self = Self {
+        gravity: 22.0,
+        speed_x: 0.4,
+        speed_y: 0.8,
+        min_size: 1.5,
+        min_alpha: 0.4,
+        rotation_speed: 0.5,
+        streak_length: 0.05,
+        speed_x_jitter: 0.3,
+        speed_y_jitter: 0.5,
+        size_range: 2.0,
+        sway: 0.2,
+        streak_share: 0.5,
+        bounce: 0.0,
+    };
+
+    pub const HAIL: Self = Self {
+        gravity: 30.0,
+        speed_x: 0.1,
+        speed_y: 1.0,
+        min_size: 3.5,
+        min_alpha: 0.8,
+        rotation_speed: 0.5,
+        streak_length: 0.0,
+        speed_x_jitter: 0.1,
+        speed_y_jitter: 0.3,
+        size_range: 3.0,
+        sway: 0.05,
+        streak_share: 0.0,
+        bounce: 4.0,
+    };
+}
@@ -0,0 +1,369 @@
+//! Procedural snowflake crystals
+//!
+//! Rasterizes six-fold symmetric dendritic crystals into RGBA pixel buffers
+//! without touching the DOM, so the output only depends on the seed.
+
+use crate::config::get_f32;
+use crate::constants::*;
+use crate::texture::AtlasLayout;
+use wasm_bindgen::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProceduralConfig {
+    pub seed: u32,
+    /// Number of distinct crystals in the atlas.
+    pub count: u32,
+    /// Cell size in pixels.
+    pub size: u32,
+}
+
+impl Default for ProceduralConfig {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            count: DEFAULT_PROCEDURAL_COUNT,
+            size: DEFAULT_PROCEDURAL_SIZE,
+        }
+    }
+}
+
+impl ProceduralConfig {
+    /// Accepts `true` for defaults or `{ seed, count, size }`.
+    pub fn from_js(value: &JsValue) -> Option<Self> {
+        if value.as_bool() == Some(true) {
+            return Some(Self::default());
+        }
+        if !value.is_object() {
+            return None;
+        }
+        let mut config = Self::default();
+        if let Some(v) = get_f32(value, "seed") {
+            config.seed = v as u32;
+        }
+        if let Some(v) = get_f32(value, "count") {
+            config.count = (v as u32).clamp(1, PROCEDURAL_MAX_COUNT);
+        }
+        if let Some(v) = get_f32(value, "size") {
+            config.size = (v as u32).clamp(ATLAS_MIN_CELL_SIZE, ATLAS_MAX_CELL_SIZE);
+        }
+        Some(config)
+    }
+}
+
+/// Small deterministic generator (SplitMix32), identical on every platform.
+#[derive(Clone, Debug)]
+pub struct Rng(u32);
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9);
+        let mut z = self.0;
+        z = (z ^ (z >> 16)).wrapping_mul(0x85eb_ca6b);
+        z = (z ^ (z >> 13)).wrapping_mul(0xc2b2_ae35);
+        z ^ (z >> 16)
+    }
+
+    /// Uniform in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + self.next_f32() * (max - min)
+    }
+}
+
+/// Line segment with a half-width, in unit crystal coordinates.
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    a: [f32; 2],
+    b: [f32; 2],
+    half_width: f32,
+}
+
+impl Segment {
+    fn distance(&self, p: [f32; 2]) -> f32 {
+        let (ab, ap) = (
+            [self.b[0] - self.a[0], self.b[1] - self.a[1]],
+            [p[0] - self.a[0], p[1] - self.a[1]],
+        );
+        let len_sq = ab[0] * ab[0] + ab[1] * ab[1];
+        let t = if len_sq > 0.0 {
+            ((ap[0] * ab[0] + ap[1] * ab[1]) / len_sq).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let (dx, dy) = (ap[0] - ab[0] * t, ap[1] - ab[1] * t);
+        (dx * dx + dy * dy).sqrt() - self.half_width
+    }
+
+    fn rotated(&self, angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        let rot = |p: [f32; 2]| [p[0] * c - p[1] * s, p[0] * s + p[1] * c];
+        Self {
+            a: rot(self.a),
+            b: rot(self.b),
+            half_width: self.half_width,
+        }
+    }
+}
+
+/// Random crystal: a hexagonal core plus six identical arms, each with
+/// mirrored side branches at 60° that may carry smaller twigs.
+struct Crystal {
+    core: f32,
+    segments: Vec<Segment>,
+}
+
+impl Crystal {
+    fn generate(rng: &mut Rng) -> Self {
+        let arm_length = rng.range(0.8, 0.95);
+        let width = rng.range(0.025, 0.05);
+        let core = rng.range(0.06, 0.22);
+
+        // One arm along +X; branches leave at ±60°
+        let mut arm = vec![Segment {
+            a: [0.0, 0.0],
+            b: [arm_length, 0.0],
+            half_width: width,
+        }];
+        let (s60, c60) = 60f32.to_radians().sin_cos();
+        let branches = 2 + (rng.next_u32() % 4) as usize;
+        for i in 0..branches {
+            let t = 0.25 + 0.6 * (i as f32 + rng.range(0.0, 0.6)) / branches as f32;
+            let x = t * arm_length;
+            let length = rng.range(0.15, 0.45) * (1.0 - t * 0.55);
+            let branch_width = width * rng.range(0.6, 0.9);
+            let twigs = rng.next_f32() < 0.5;
+            let twig_at = rng.range(0.3, 0.7);
+            let twig_length = length * rng.range(0.25, 0.45);
+
+            for side in [1.0, -1.0] {
+                let dir = [c60, s60 * side];
+                let end = [x + dir[0] * length, dir[1] * length];
+                arm.push(Segment {
+                    a: [x, 0.0],
+                    b: end,
+                    half_width: branch_width,
+                });
+                if twigs {
+                    // Twigs run parallel to the main arm, like real dendrites
+                    let base = [x + dir[0] * length * twig_at, dir[1] * length * twig_at];
+                    arm.push(Segment {
+                        a: base,
+                        b: [base[0] + twig_length, base[1]],
+                        half_width: branch_width * 0.7,
+                    });
+                }
+            }
+        }
+
+        let segments = (0..6)
+            .flat_map(|k| {
+                let angle = k as f32 * 60f32.to_radians();
+                arm.iter().map(move |s| s.rotated(angle))
+            })
+            .collect();
+        Self { core, segments }
+    }
+
+    /// Signed distance in unit coordinates, negative inside.
+    fn distance(&self, p: [f32; 2]) -> f32 {
+        // Hexagon with flat sides facing the gaps between arms
+        let (x, y) = (p[0].abs(), p[1].abs());
+        let (s30, c30) = 30f32.to_radians().sin_cos();
+        let hex = (x * c30 + y * s30).max(y) - self.core;
+
+        self.segments
+            .iter()
+            .map(|s| s.distance(p))
+            .fold(hex, f32::min)
+    }
+}
+
+/// Renders one crystal into an RGBA buffer of `size`×`size` pixels. Color is
+/// white everywhere; alpha holds the anti-aliased coverage.
+pub fn generate_crystal(seed: u32, size: u32) -> Vec<u8> {
+    let mut pixels = vec![0u8; (size * size * 4) as usize];
+    draw_crystal(&mut Rng::new(seed), &mut pixels, size, (0, 0), size);
+    pixels
+}
+
+/// Pixel buffer of an atlas of crystals.
+#[derive(Clone, Debug)]
+pub struct CrystalAtlas {
+    pub width: u32,
+    pub height: u32,
+    pub layout: AtlasLayout,
+    pub pixels: Vec<u8>,
+}
+
+/// Renders `count` different crystals, up to `PROCEDURAL_MAX_COUNT`, into an
+/// atlas with `cell`-pixel cells.
+pub fn generate_atlas(seed: u32, count: u32, cell: u32) -> CrystalAtlas {
+    let count = count.clamp(1, PROCEDURAL_MAX_COUNT);
+    let layout = AtlasLayout::for_count(count as usize);
+    let (width, height) = (layout.columns * cell, layout.rows * cell);
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+
+    let mut rng = Rng::new(seed);
+    for i in 0..count {
+        let mut crystal_rng = Rng::new(rng.next_u32());
+        let origin = layout.cell_origin(i, cell);
+        draw_crystal(&mut crystal_rng, &mut pixels, width, origin, cell);
+    }
+
+    CrystalAtlas {
+        width,
+        height,
+        layout,
+        pixels,
+    }
+}
+
+fn draw_crystal(rng: &mut Rng, pixels: &mut [u8], stride: u32, origin: (u32, u32), cell: u32) {
+    let crystal = Crystal::generate(rng);
+    // Keep `ATLAS_CELL_PADDING` pixels free so arm tips are not clipped by the cell
+    let radius = cell as f32 * 0.5 - ATLAS_CELL_PADDING as f32;
+    let center = cell as f32 * 0.5;
+    let pixel = 1.0 / radius;
+
+    for y in 0..cell {
+        for x in 0..cell {
+            let p = [
+                (x as f32 + 0.5 - center) / radius,
+                (y as f32 + 0.5 - center) / radius,
+            ];
+            let coverage = (0.5 - crystal.distance(p) / pixel).clamp(0.0, 1.0);
+            let i = (((origin.1 + y) * stride + origin.0 + x) * 4) as usize;
+            pixels[i..i + 4].copy_from_slice(&[255, 255, 255, (coverage * 255.0).round() as u8]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rng_is_deterministic() {
+        let (mut a, mut b) = (Rng::new(7), Rng::new(7));
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+        let mut c = Rng::new(8);
+        assert_ne!(Rng::new(7).next_u32(), c.next_u32());
+    }
+
+    #[test]
+    fn rng_floats_stay_in_range() {
+        let mut rng = Rng::new(3);
+        for _ in 0..1000 {
+            let v = rng.next_f32();
+            assert!((0.0..1.0).contains(&v));
+            let v = rng.range(-2.0, 5.0);
+            assert!((-2.0..5.0).contains(&v));
+        }
+    }
+
+    #[test]
+    fn crystal_depends_only_on_seed() {
+        assert_eq!(generate_crystal(42, 32), generate_crystal(42, 32));
+        assert_ne!(generate_crystal(42, 32), generate_crystal(43, 32));
+    }
+
+    #[test]
+    fn crystal_is_white_with_coverage_in_alpha() {
+        let pixels = generate_crystal(5, 32);
+        assert_eq!(pixels.len(), 32 * 32 * 4);
+        assert!(pixels.chunks_exact(4).all(|p| p[..3] == [255, 255, 255]));
+        // Core is opaque, corners are empty
+        let alpha = |x: usize, y: usize| pixels[(y * 32 + x) * 4 + 3];
+        assert_eq!(alpha(16, 16), 255);
+        assert_eq!(alpha(0, 0), 0);
+        assert_eq!(alpha(31, 31), 0);
+    }
+
+    #[test]
+    fn crystal_has_six_fold_symmetry() {
+        for seed in 0..8 {
+            let crystal = Crystal::generate(&mut Rng::new(seed));
+            let (s, c) = 60f32.to_radians().sin_cos();
+            for i in 0..200 {
+                let angle = i as f32 * 0.37;
+                let radius = (i % 20) as f32 / 20.0;
+                let p = [angle.cos() * radius, angle.sin() * radius];
+                let rotated = [p[0] * c - p[1] * s, p[0] * s + p[1] * c];
+                let mirrored = [p[0], -p[1]];
+                let d = crystal.distance(p);
+                assert!((d - crystal.distance(rotated)).abs() < 1e-4);
+                assert!((d - crystal.distance(mirrored)).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn crystal_pixels_are_mirror_symmetric() {
+        let size = 48;
+        let pixels = generate_crystal(11, size);
+        let alpha = |x: u32, y: u32| pixels[((y * size + x) * 4 + 3) as usize] as i32;
+        for y in 0..size {
+            for x in 0..size {
+                assert!((alpha(x, y) - alpha(size - 1 - x, y)).abs() <= 1);
+                assert!((alpha(x, y) - alpha(x, size - 1 - y)).abs() <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn atlas_lays_out_cells() {
+        let atlas = generate_atlas(9, 5, 32);
+        assert_eq!((atlas.layout.columns, atlas.layout.rows), (3, 2));
+        assert_eq!((atlas.width, atlas.height), (96, 64));
+        assert_eq!(atlas.pixels.len(), 96 * 64 * 4);
+
+        // Every used cell holds a crystal centered in it, the spare one is empty
+        let alpha = |x: u32, y: u32| atlas.pixels[((y * atlas.width + x) * 4 + 3) as usize];
+        for i in 0..6 {
+            let (x, y) = atlas.layout.cell_origin(i, 32);
+            assert_eq!(alpha(x + 16, y + 16) == 255, i < 5, "cell {i}");
+        }
+    }
+
+    #[test]
+    fn atlas_is_deterministic() {
+        let (a, b) = (generate_atlas(1, 4, 32), generate_atlas(1, 4, 32));
+        assert_eq!(a.pixels, b.pixels);
+        assert_ne!(a.pixels, generate_atlas(2, 4, 32).pixels);
+    }
+
+    #[test]
+    fn atlas_cells_differ() {
+        let atlas = generate_atlas(3, 2, 32);
+        let cell = |i: u32| {
+            let (x0, y0) = atlas.layout.cell_origin(i, 32);
+            (0..32)
+                .flat_map(|y| (0..32).map(move |x| (x0 + x, y0 + y)))
+                .map(|(x, y)| atlas.pixels[((y * atlas.width + x) * 4 + 3) as usize])
+                .collect::<Vec<_>>()
+        };
+        assert_ne!(cell(0), cell(1));
+    }
+
+    #[test]
+    fn atlas_count_is_clamped() {
+        let max = PROCEDURAL_MAX_COUNT as usize;
+        let atlas = generate_atlas(1, 1000, ATLAS_MIN_CELL_SIZE);
+        assert_eq!(atlas.layout, AtlasLayout::for_count(max));
+
+        let atlas = generate_atlas(1, 0, ATLAS_MIN_CELL_SIZE);
+        assert_eq!(
+            (atlas.width, atlas.height),
+            (ATLAS_MIN_CELL_SIZE, ATLAS_MIN_CELL_SIZE)
+        );
+    }
+}
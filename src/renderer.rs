@@ -1,33 +1,88 @@
 //! Snowfall rendering module
 
 use js_sys::Math;
+use std::collections::HashMap;
 use wasm_bindgen::prelude::*;
-#[cfg(feature = "configurable")]
-use web_sys::{HtmlImageElement, WebGlTexture};
 use web_sys::{
-    console, HtmlCanvasElement, WebGlBuffer, WebGlProgram,
-    WebGlRenderingContext as GL, WebGlShader, WebGlUniformLocation,
+    console, HtmlCanvasElement, WebGlBuffer, WebGlProgram, WebGlRenderingContext as GL,
+    WebGlShader, WebGlTexture, WebGlUniformLocation,
 };
 
-use crate::config::{SnowConfig, WindState};
+use crate::background::BackgroundLayer;
+#[cfg(feature = "configurable")]
+use crate::background::{Background, BackgroundFit};
+use crate::camera::{Camera, OrbitMode};
+use crate::config::{FogMode, InitialTexture, SnowConfig, WindState};
 use crate::constants::*;
-use crate::shaders::{FRAGMENT_SHADER_SOURCE, VERTEX_SHADER_SOURCE};
+#[cfg(feature = "configurable")]
+use crate::emitter::EmitterConfig;
+use crate::emitter::{Emitter, SpawnSpace};
+use crate::formation::{Formation, FormationOptions, Silhouette};
+#[cfg(feature = "configurable")]
+use crate::globe::GlobeConfig;
+use crate::globe::{GlassLayer, GlobeState};
+#[cfg(feature = "configurable")]
+use crate::ground::GroundConfig;
+use crate::ground::GroundLayer;
+use crate::hooks::{HookedSource, ShaderHooks};
+#[cfg(feature = "configurable")]
+use crate::mask::MaskConfig;
+use crate::mask::OcclusionMask;
+use crate::palette::{weighted_index, Palette};
+use crate::postprocess::PostProcessor;
+#[cfg(feature = "configurable")]
+use crate::precipitation::Precipitation;
+use crate::procedural::{generate_atlas, ProceduralConfig};
+use crate::shaders::ShaderFeatures;
+use crate::texture::AtlasLayout;
+use crate::texture::{compose_atlas, AtlasEntry, TextureSource};
+#[cfg(feature = "configurable")]
+use crate::texture::{SpriteSheet, TextureOptions};
+use crate::theme::BlendMode;
+#[cfg(feature = "configurable")]
+use crate::theme::{Shape, Theme};
 
 // Uniform locations are read by WebGL. Suppress dead_code warning.
 #[allow(dead_code)]
 struct Uniforms {
     time: Option<WebGlUniformLocation>,
     projection: Option<WebGlUniformLocation>,
+    view: Option<WebGlUniformLocation>,
     world_size: Option<WebGlUniformLocation>,
     gravity: Option<WebGlUniformLocation>,
     wind: Option<WebGlUniformLocation>,
+    wind_direction: Option<WebGlUniformLocation>,
+    wind_velocity: Option<WebGlUniformLocation>,
     resolution: Option<WebGlUniformLocation>,
     rotation_speed: Option<WebGlUniformLocation>,
+    scroll: Option<WebGlUniformLocation>,
+    focal_distance: Option<WebGlUniformLocation>,
+    aperture: Option<WebGlUniformLocation>,
+    fog_mode: Option<WebGlUniformLocation>,
+    fog_params: Option<WebGlUniformLocation>,
+    fog_color: Option<WebGlUniformLocation>,
+    streak_length: Option<WebGlUniformLocation>,
+    bounce: Option<WebGlUniformLocation>,
+    floor: Option<WebGlUniformLocation>,
+    globe: Option<WebGlUniformLocation>,
+    globe_energy: Option<WebGlUniformLocation>,
+    globe_swirl: Option<WebGlUniformLocation>,
+    shape: Option<WebGlUniformLocation>,
+    tumble: Option<WebGlUniformLocation>,
+    twinkle: Option<WebGlUniformLocation>,
+    sparkle: Option<WebGlUniformLocation>,
+    atlas_grid: Option<WebGlUniformLocation>,
+    sprite_grid: Option<WebGlUniformLocation>,
+    sprite_frame: Option<WebGlUniformLocation>,
     point_scale: Option<WebGlUniformLocation>,
     texture: Option<WebGlUniformLocation>,
-    use_texture: Option<WebGlUniformLocation>,
+    premultiplied: Option<WebGlUniformLocation>,
     color_tint: Option<WebGlUniformLocation>,
-    use_color_tint: Option<WebGlUniformLocation>,
+    mask: Option<WebGlUniformLocation>,
+    mask_scale: Option<WebGlUniformLocation>,
+    formation: Option<WebGlUniformLocation>,
+    emitter_lifetime: Option<WebGlUniformLocation>,
+    emitter_spread: Option<WebGlUniformLocation>,
 }
 
 impl Uniforms {
@@ -36,16 +91,42 @@ impl Uniforms {
         Self {
             time: get("u_time"),
             projection: get("u_projection"),
+            view: get("u_view"),
             world_size: get("u_worldSize"),
             gravity: get("u_gravity"),
             wind: get("u_wind"),
+            wind_direction: get("u_windDirection"),
+            wind_velocity: get("u_windVelocity"),
             resolution: get("u_resolution"),
             rotation_speed: get("u_rotationSpeed"),
+            scroll: get("u_scroll"),
+            focal_distance: get("u_focalDistance"),
+            aperture: get("u_aperture"),
+            fog_mode: get("u_fogMode"),
+            fog_params: get("u_fogParams"),
+            fog_color: get("u_fogColor"),
+            streak_length: get("u_streakLength"),
+            bounce: get("u_bounce"),
+            floor: get("u_floor"),
+            globe: get("u_globe"),
+            globe_energy: get("u_globeEnergy"),
+            globe_swirl: get("u_globeSwirl"),
+            shape: get("u_shape"),
+            tumble: get("u_tumble"),
+            twinkle: get("u_twinkle"),
+            sparkle: get("u_sparkle"),
+            atlas_grid: get("u_atlasGrid"),
+            sprite_grid: get("u_spriteGrid"),
+            sprite_frame: get("u_spriteFrame"),
             point_scale: get("u_pointScale"),
             texture: get("u_texture"),
-            use_texture: get("u_useTexture"),
+            premultiplied: get("u_premultiplied"),
             color_tint: get("u_colorTint"),
-            use_color_tint: get("u_useColorTint"),
+            mask: get("u_mask"),
+            mask_scale: get("u_maskScale"),
+            formation: get("u_formation"),
+            emitter_lifetime: get("u_emitterLifetime"),
+            emitter_spread: get("u_emitterSpread"),
         }
     }
 }
@@ -55,6 +136,12 @@ pub struct RenderResult {
     pub fps: u32,
     pub time: f32,
     pub wind: f32,
+    pub wind_x: f32,
+    pub wind_y: f32,
+    pub wind_z: f32,
+    pub wind_angle: f32,
+    pub wind_elevation: f32,
+    pub wind_strength: f32,
     pub particle_count: u32,
 }
 
@@ -67,6 +154,7 @@ struct Buffers {
     rotation: WebGlBuffer,
     size: WebGlBuffer,
     speed: WebGlBuffer,
+    variation: WebGlBuffer,
 }
 
 #[wasm_bindgen]
@@ -74,10 +162,40 @@ pub struct SnowfallShader {
     gl: GL,
     canvas: HtmlCanvasElement,
     program: WebGlProgram,
+    /// Features compiled into `program`.
+    features: ShaderFeatures,
+    /// Compiled variants by feature set, including `program`.
+    programs: HashMap<ShaderFeatures, WebGlProgram>,
+    /// Variant that failed to compile, not retried until the features or
+    /// hooks change.
+    failed_features: Option<ShaderFeatures>,
     uniforms: Uniforms,
     buffers: Option<Buffers>,
-    #[cfg(feature = "configurable")]
     texture: Option<WebGlTexture>,
+    /// Source of the current texture, kept to re-upload on option changes.
+    texture_source: Option<TextureSource>,
+    /// Texture waiting for its images to load.
+    pending_texture: Option<PendingTexture>,
+    /// Atlas shape weights, empty for a single texture.
+    shape_weights: Vec<f32>,
+    /// Bloom and vignette chain, present while either is enabled.
+    post: Option<PostProcessor>,
+    /// Layer drawing `config.background`, absent while it is transparent.
+    background: Option<BackgroundLayer>,
+    /// Ground plane, present while `config.ground` is set.
+    ground: Option<GroundLayer>,
+    /// Shake energy of the snow globe.
+    globe: GlobeState,
+    /// Glass over the snow globe, present while `config.globe` is set.
+    glass: Option<GlassLayer>,
+    /// Rasterized `config.mask`.
+    mask: Option<OcclusionMask>,
+    /// Shape the flakes are gathering into, see `form_text`.
+    formation: Option<Formation>,
+    /// Spawn pool, present while `config.emitter` is used.
+    emitter: Option<Emitter>,
+    /// Last position from `set_cursor_position`.
+    cursor: Option<[f32; 2]>,
 
     base_particle_count: u32,
     actual_particle_count: i32,
@@ -86,6 +204,7 @@ pub struct SnowfallShader {
     last_time: f32,
 
     wind: WindState,
+    camera: Camera,
     config: SnowConfig,
 
     frame_count: u32,
@@ -95,6 +214,50 @@ pub struct SnowfallShader {
     world_width: f32,
     world_height: f32,
     world_depth: f32,
+
+    scroll_offset: Option<f32>,
+}
+
+/// Attribute names in location order.
+const ATTRIBUTES: [&str; 7] = [
+    "a_position",
+    "a_color",
+    "a_rotation",
+    "a_speed",
+    "a_size",
+    "a_variation",
+    "a_target",
+];
+
+/// Texture set before its images finished loading.
+enum PendingTexture {
+    Single(TextureSource),
+    Atlas(Vec<AtlasEntry>),
+}
+
+// From EXT_texture_filter_anisotropic, which web-sys doesn't expose on GL
+const TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
+
+/// Document scroll position and extents in CSS pixels.
+struct ScrollMetrics {
+    offset: f32,
+    viewport: f32,
+    total: f32,
+}
+
+impl ScrollMetrics {
+    fn read() -> Option<Self> {
+        let window = web_sys::window()?;
+        let offset = window.scroll_y().ok()? as f32;
+        let viewport = window.inner_height().ok()?.as_f64()? as f32;
+        let total = window.document()?.document_element()?.scroll_height() as f32;
+        Some(Self {
+            offset,
+            viewport,
+            total,
+        })
+    }
 }
 
 #[wasm_bindgen]
@@ -103,7 +266,7 @@ impl SnowfallShader {
     pub fn new(canvas_id: &str, config_val: JsValue) -> Result<Self, JsValue> {
         console::log_1(&"[RemnaSnow] Initializing WASM module...".into());
 
-        let config = SnowConfig::from_js(config_val);
+        let mut config = SnowConfig::from_js(config_val);
 
         let document = web_sys::window()
             .and_then(|w| w.document())
@@ -120,10 +283,20 @@ impl SnowfallShader {
             .dyn_into()?;
 
         gl.enable(GL::BLEND);
-        gl.blend_func(GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA);
         gl.clear_color(0.0, 0.0, 0.0, 0.0);
 
-        let program = Self::create_program(&gl)?;
+        // Textures are uploaded later and switch to their variant then
+        let features = Self::shader_features(&config, false);
+        let program = match Self::create_program(&gl, &config.shader_hooks, features) {
+            Ok(program) => program,
+            Err(err) if !config.shader_hooks.is_empty() => {
+                // Broken hooks fall back to the built-in shaders
+                console::error_1(&err);
+                config.shader_hooks = ShaderHooks::default();
+                Self::create_program(&gl, &config.shader_hooks, features)?
+            }
+            Err(err) => return Err(err),
+        };
         gl.use_program(Some(&program));
 
         let uniforms = Uniforms::load(&gl, &program);
@@ -131,16 +304,31 @@ impl SnowfallShader {
         let mut shader = Self {
             gl,
             canvas,
-            program,
+            program: program.clone(),
+            features,
+            programs: HashMap::from([(features, program)]),
+            failed_features: None,
             uniforms,
             buffers: None,
-            #[cfg(feature = "configurable")]
             texture: None,
+            texture_source: None,
+            pending_texture: None,
+            shape_weights: Vec::new(),
+            post: None,
+            background: None,
+            ground: None,
+            globe: GlobeState::default(),
+            glass: None,
+            mask: None,
+            formation: None,
+            emitter: None,
+            cursor: None,
             base_particle_count: config.particle_count,
             actual_particle_count: 0,
             time: 0.0,
             last_time: 0.0,
-            wind: WindState::default(),
+            wind: WindState::from_config(&config),
+            camera: Camera::new(config.camera, config.depth),
             config: config.clone(),
             frame_count: 0,
             fps_last_time: 0.0,
@@ -148,30 +336,42 @@ impl SnowfallShader {
             world_width: 0.0,
             world_height: WORLD_HEIGHT,
             world_depth: config.depth,
+            scroll_offset: None,
         };
 
         shader.resize()?;
+        if let Err(err) = shader.update_post() {
+            // Rendering without post-processing beats not rendering at all
+            console::error_1(&err);
+        }
+        if let Err(err) = shader.update_background() {
+            console::error_1(&err);
+        }
+        if let Err(err) = shader.update_ground() {
+            console::error_1(&err);
+        }
+        if let Err(err) = shader.update_glass() {
+            console::error_1(&err);
+        }
+        if let Err(err) = shader.update_mask() {
+            // An empty mask texture would hide every flake
+            console::error_1(&err);
+            shader.config.mask = None;
+        }
         shader.setup_buffers()?;
+        shader.apply_blend();
 
-        #[cfg(feature = "configurable")]
-        {
-            if let Some(c) = &config.color {
-                if c.len() >= 3 {
-                    shader.set_color(c[0], c[1], c[2]);
-                }
-            } else {
-                shader.set_color(1.0, 1.0, 1.0);
-            }
-            
-            if let Some(tex) = &config.texture {
-                shader.set_texture(tex)?;
+        shader.update_color();
+
+        match config.appearance().texture {
+            InitialTexture::Atlas(entries) => shader.apply_texture_atlas(entries)?,
+            InitialTexture::Procedural(procedural) => {
+                shader.apply_procedural_texture(procedural)?
             }
+            InitialTexture::Single(texture) => shader.apply_texture(texture)?,
+            InitialTexture::None => {}
         }
-        
-        #[cfg(not(feature = "configurable"))]
-        {
-            shader.set_uniform_3f(&shader.uniforms.color_tint, 1.0, 1.0, 1.0);
-        }
+        shader.use_variant()?;
 
         console::log_1(
             &format!(
@@ -183,13 +383,26 @@ impl SnowfallShader {
         Ok(shader)
     }
 
-    fn create_program(gl: &GL) -> Result<WebGlProgram, JsValue> {
-        let vs = Self::compile_shader(gl, GL::VERTEX_SHADER, VERTEX_SHADER_SOURCE)?;
-        let fs = Self::compile_shader(gl, GL::FRAGMENT_SHADER, FRAGMENT_SHADER_SOURCE)?;
+    fn create_program(
+        gl: &GL,
+        hooks: &ShaderHooks,
+        features: ShaderFeatures,
+    ) -> Result<WebGlProgram, JsValue> {
+        hooks
+            .validate()
+            .map_err(|e| format!("Shader hook error: {e}"))?;
+        let vs = Self::compile_shader(gl, GL::VERTEX_SHADER, &hooks.vertex_source(features))?;
+        let fs = Self::compile_shader(gl, GL::FRAGMENT_SHADER, &hooks.fragment_source(features))?;
 
         let program = gl.create_program().ok_or("Failed to create program")?;
         gl.attach_shader(&program, &vs);
         gl.attach_shader(&program, &fs);
+        // Fixed locations keep the particle buffers bound across programs
+        for (index, name) in ATTRIBUTES.iter().enumerate() {
+            gl.bind_attrib_location(&program, index as u32, name);
+        }
+        // Emitter variants read positions and spawn times together
+        gl.bind_attrib_location(&program, 0, "a_spawn");
         gl.link_program(&program);
 
         if !gl
@@ -203,11 +416,15 @@ impl SnowfallShader {
         Ok(program)
     }
 
-    fn compile_shader(gl: &GL, shader_type: u32, source: &str) -> Result<WebGlShader, JsValue> {
+    fn compile_shader(
+        gl: &GL,
+        shader_type: u32,
+        source: &HookedSource,
+    ) -> Result<WebGlShader, JsValue> {
         let shader = gl
             .create_shader(shader_type)
             .ok_or("Failed to create shader")?;
-        gl.shader_source(&shader, source);
+        gl.shader_source(&shader, &source.source);
         gl.compile_shader(&shader);
 
         if !gl
@@ -217,23 +434,36 @@ impl SnowfallShader {
         {
             let info = gl.get_shader_info_log(&shader).unwrap_or_default();
             gl.delete_shader(Some(&shader));
+            let info = source.map_log(&info);
             return Err(format!("Shader compilation error: {info}").into());
         }
         Ok(shader)
     }
 
     fn create_buffer(&self, name: &str, data: &[f32], size: i32) -> Result<WebGlBuffer, JsValue> {
+        self.create_buffer_with_usage(name, data, size, GL::STATIC_DRAW)
+    }
+
+    /// `create_buffer` with a usage hint, such as `DYNAMIC_DRAW` for data
+    /// rewritten every frame.
+    fn create_buffer_with_usage(
+        &self,
+        name: &str,
+        data: &[f32],
+        size: i32,
+        usage: u32,
+    ) -> Result<WebGlBuffer, JsValue> {
         let buffer = self.gl.create_buffer().ok_or("Failed to create buffer")?;
         self.gl.bind_buffer(GL::ARRAY_BUFFER, Some(&buffer));
 
         unsafe {
             let array = js_sys::Float32Array::view(data);
             self.gl
-                .buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &array, GL::STATIC_DRAW);
+                .buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &array, usage);
         }
 
-        let location = self.gl.get_attrib_location(&self.program, name);
-        if location >= 0 {
+        // Locations are fixed, so the current program may lack the attribute
+        if let Some(location) = ATTRIBUTES.iter().position(|&a| a == name) {
             let loc = location as u32;
             self.gl.enable_vertex_attrib_array(loc);
             self.gl
@@ -243,6 +473,8 @@ impl SnowfallShader {
     }
 
     fn setup_buffers(&mut self) -> Result<(), JsValue> {
+        // Formation targets are per particle
+        self.end_formation();
         let (w, h) = (self.canvas.width() as f32, self.canvas.height() as f32);
         let aspect = if h > 0.0 { w / h } else { 1.0 };
 
@@ -252,61 +484,202 @@ impl SnowfallShader {
         self.world_depth = depth;
 
         let count = (aspect * self.base_particle_count as f32) as usize;
-        let (mut positions, mut colors, mut sizes, mut rotations, mut speeds) = (
+        let (mut positions, mut colors, mut sizes, mut rotations, mut speeds, mut variations) = (
             Vec::with_capacity(count * 3),
             Vec::with_capacity(count * 4),
             Vec::with_capacity(count),
             Vec::with_capacity(count * 3),
             Vec::with_capacity(count * 3),
+            Vec::with_capacity(count * 4),
         );
 
         let rand = || Math::random() as f32;
         let pi2 = std::f32::consts::TAU;
+        let preset = self.config.precipitation.preset();
+        let palette = self
+            .config
+            .palette
+            .clone()
+            .unwrap_or_else(|| Palette::uniform(self.config.theme.preset().palette));
 
         for _ in 0..count {
+            let z = -depth + rand() * depth * 2.0;
             positions.extend_from_slice(&[
                 -width + rand() * width * 2.0,
                 -height + rand() * height * 2.0,
-                -depth + rand() * depth * 2.0,
+                z,
             ]);
 
             speeds.extend_from_slice(&[
-                (self.config.speed_x + rand() * 0.4) * self.config.direction_x,
-                self.config.speed_y + rand() * 0.5,
+                self.config.speed_x + rand() * preset.speed_x_jitter,
+                self.config.speed_y + rand() * preset.speed_y_jitter,
                 rand() * 2.0,
             ]);
 
             rotations.extend_from_slice(&[
                 rand() * pi2,
                 rand() * 5.0 * self.config.rotation_speed,
-                rand() * 3.0,
+                rand() * 3.0 * preset.sway,
             ]);
 
+            let size_t = rand();
+            sizes.push(self.config.min_size + size_t * preset.size_range);
+
             let alpha = self.config.min_alpha + rand() * (1.0 - self.config.min_alpha);
-            colors.extend_from_slice(&[1.0, 1.0, 1.0, alpha]);
+            let nearness = (z + depth) / (2.0 * depth);
+            let [r, g, b] = palette.sample(rand(), nearness, size_t);
+            colors.extend_from_slice(&[r, g, b, alpha]);
 
-            sizes.push(self.config.min_size + rand() * 4.5);
+            let streak = if rand() < preset.streak_share {
+                1.0
+            } else {
+                0.0
+            };
+            let shape = weighted_index(&self.shape_weights, rand()) as f32;
+            let twinkle_rate = 1.0 + rand() * 3.0;
+            variations.extend_from_slice(&[streak, shape, rand(), twinkle_rate]);
         }
 
+        // Emitted flakes replace the box positions with spawn positions and
+        // times, rewritten every frame
+        let (positions, position_size, position_usage) = if Self::uses_emitter(&self.config) {
+            let wind_response = speeds.iter().step_by(3).copied().collect();
+            self.emitter = Some(Emitter::new(wind_response));
+            (Emitter::pool(count), 4, GL::DYNAMIC_DRAW)
+        } else {
+            self.emitter = None;
+            (positions, 3, GL::STATIC_DRAW)
+        };
+
         self.buffers = Some(Buffers {
-            position: self.create_buffer("a_position", &positions, 3)?,
+            position: self.create_buffer_with_usage(
+                "a_position",
+                &positions,
+                position_size,
+                position_usage,
+            )?,
             color: self.create_buffer("a_color", &colors, 4)?,
             rotation: self.create_buffer("a_rotation", &rotations, 3)?,
             size: self.create_buffer("a_size", &sizes, 1)?,
             speed: self.create_buffer("a_speed", &speeds, 3)?,
+            variation: self.create_buffer("a_variation", &variations, 4)?,
         });
 
-        self.set_uniform_3f(&self.uniforms.world_size, width, height, depth);
+        self.actual_particle_count = count as i32;
+        self.update_particle_uniforms();
+        Ok(())
+    }
+
+    /// Uniforms derived from the config that `setup_buffers` keeps in sync.
+    fn update_particle_uniforms(&mut self) {
+        let preset = self.config.precipitation.preset();
+        self.set_uniform_3f(
+            &self.uniforms.world_size,
+            self.world_width,
+            self.world_height,
+            self.world_depth,
+        );
         self.set_uniform_1f(
             &self.uniforms.gravity,
             self.config.gravity * self.config.direction_y,
         );
         self.set_uniform_1f(&self.uniforms.rotation_speed, self.config.rotation_speed);
+        self.set_uniform_1f(&self.uniforms.focal_distance, self.config.focal_distance);
+        self.set_uniform_1f(&self.uniforms.aperture, self.config.aperture);
+        self.set_uniform_1f(&self.uniforms.streak_length, self.config.streak_length);
+        self.set_uniform_1f(&self.uniforms.bounce, preset.bounce);
+        self.set_uniform_1f(&self.uniforms.floor, self.floor());
+        if let (Some(l), Some(globe)) = (&self.uniforms.globe, &self.config.globe) {
+            let [x, y, z] = globe.center;
+            self.gl.uniform4f(Some(l), x, y, z, globe.radius);
+        }
+        self.update_globe_energy();
+        if let Some(emitter) = &self.config.emitter {
+            self.set_uniform_1f(&self.uniforms.emitter_lifetime, emitter.lifetime);
+            self.set_uniform_1f(&self.uniforms.emitter_spread, emitter.spread);
+        }
+        self.set_uniform_1f(&self.uniforms.tumble, self.config.tumble);
+        self.set_uniform_1f(&self.uniforms.twinkle, self.config.twinkle);
+        self.set_uniform_1f(&self.uniforms.sparkle, self.config.sparkle);
+        if let Some(l) = &self.uniforms.shape {
+            self.gl.uniform1i(Some(l), self.config.shape as i32);
+        }
+        self.update_atlas_grid();
+        self.update_wind_direction();
+        self.update_fog();
+    }
 
-        self.actual_particle_count = count as i32;
+    /// Shader stages the config needs.
+    fn shader_features(config: &SnowConfig, textured: bool) -> ShaderFeatures {
+        let mut features = ShaderFeatures::default();
+        features.set(ShaderFeatures::TEXTURE, textured);
+        features.set(
+            ShaderFeatures::COLOR_TINT,
+            config.appearance().tint.is_some(),
+        );
+        features.set(ShaderFeatures::FOG, config.fog.mode != FogMode::Off);
+        features.set(ShaderFeatures::DEPTH_OF_FIELD, config.aperture > 0.0);
+        features.set(ShaderFeatures::STREAKS, config.streak_length > 0.0);
+        features.set(ShaderFeatures::TUMBLE, config.tumble > 0.0);
+        features.set(
+            ShaderFeatures::SPARKLE,
+            config.twinkle > 0.0 || config.sparkle > 0.0,
+        );
+        features.set(ShaderFeatures::GROUND, config.ground.is_some());
+        features.set(ShaderFeatures::GLOBE, config.globe.is_some());
+        features.set(ShaderFeatures::MASK, config.mask.is_some());
+        features.set(ShaderFeatures::EMITTER, Self::uses_emitter(config));
+        features
+    }
+
+    /// The snow globe places flakes itself and ignores the emitter.
+    fn uses_emitter(config: &SnowConfig) -> bool {
+        config.emitter.is_some() && config.globe.is_none()
+    }
+
+    /// Switches to the program variant matching the current config,
+    /// compiling it on first use.
+    fn use_variant(&mut self) -> Result<(), JsValue> {
+        let mut features = Self::shader_features(&self.config, self.texture.is_some());
+        features.set(ShaderFeatures::FORMATION, self.formation.is_some());
+        if features == self.features || self.failed_features == Some(features) {
+            return Ok(());
+        }
+
+        let program = match self.programs.get(&features) {
+            Some(program) => program.clone(),
+            None => {
+                // The previous program keeps drawing if this one fails
+                let program = Self::create_program(&self.gl, &self.config.shader_hooks, features)
+                    .inspect_err(|_| self.failed_features = Some(features))?;
+                self.programs.insert(features, program.clone());
+                program
+            }
+        };
+        self.features = features;
+        self.failed_features = None;
+        self.gl.use_program(Some(&program));
+        self.uniforms = Uniforms::load(&self.gl, &program);
+        self.program = program;
+        self.sync_uniforms();
         Ok(())
     }
 
+    /// Restores every uniform after switching programs.
+    fn sync_uniforms(&mut self) {
+        let (width, height) = (self.canvas.width() as f32, self.canvas.height() as f32);
+        self.set_uniform_2f(&self.uniforms.resolution, width, height);
+        self.update_projection();
+        self.update_view();
+        self.update_particle_uniforms();
+        self.update_color();
+        self.update_texture_uniforms();
+        self.update_mask_uniforms();
+        if self.config.scroll_parallax != 0.0 {
+            self.update_parallax();
+        }
+    }
+
     #[inline]
     fn set_uniform_1f(&self, loc: &Option<WebGlUniformLocation>, v: f32) {
         if let Some(l) = loc {
@@ -340,37 +713,84 @@ impl SnowfallShader {
         self.canvas.set_width(width);
         self.canvas.set_height(height);
         self.gl.viewport(0, 0, width as i32, height as i32);
+        if let Some(post) = &mut self.post {
+            post.resize(&self.gl, width, height, self.config.postprocess.scale)?;
+        }
 
-        let aspect = width as f32 / height as f32;
-        let fov = FOV_DEGREES.to_radians();
-        let f = 1.0 / (fov / 2.0).tan();
-
-        let projection = [
-            f / aspect,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            f,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            (FAR_PLANE + NEAR_PLANE) / (NEAR_PLANE - FAR_PLANE),
-            -1.0,
-            0.0,
-            0.0,
-            (2.0 * FAR_PLANE * NEAR_PLANE) / (NEAR_PLANE - FAR_PLANE),
-            0.0,
-        ];
+        self.set_uniform_2f(&self.uniforms.resolution, width as f32, height as f32);
+        self.update_projection();
+        self.update_view();
+        if self.mask.is_some() {
+            self.update_mask()?;
+        }
+        Ok(())
+    }
+
+    fn update_projection(&self) {
+        let (width, height) = (self.canvas.width() as f32, self.canvas.height() as f32);
+        let aspect = if height > 0.0 { width / height } else { 1.0 };
+        let projection = self.camera.projection(aspect);
 
         if let Some(l) = &self.uniforms.projection {
             self.gl
                 .uniform_matrix4fv_with_f32_array(Some(l), false, &projection);
         }
-        self.set_uniform_2f(&self.uniforms.resolution, width as f32, height as f32);
-        self.set_uniform_1f(&self.uniforms.point_scale, height as f32 * 0.015);
-        Ok(())
+        self.set_uniform_1f(&self.uniforms.point_scale, self.camera.point_scale(height));
+        self.set_uniform_1f(&self.uniforms.floor, self.floor());
+    }
+
+    fn update_view(&self) {
+        if let Some(l) = &self.uniforms.view {
+            self.gl
+                .uniform_matrix4fv_with_f32_array(Some(l), false, &self.camera.view());
+        }
+        self.set_uniform_1f(&self.uniforms.floor, self.floor());
+    }
+
+    /// Feeds the scroll position into the camera when orbiting with scroll.
+    fn track_scroll(&mut self) {
+        let Some(scroll) = ScrollMetrics::read() else {
+            return;
+        };
+        let range = scroll.total - scroll.viewport;
+        let progress = if range > 0.0 {
+            scroll.offset / range
+        } else {
+            0.5
+        };
+        self.camera.aim(0.0, progress * 2.0 - 1.0);
+    }
+
+    /// Shifts the particle field by the scroll offset, converted from CSS
+    /// pixels to world units so that one viewport of scrolling moves the
+    /// nearest flakes by the full world height.
+    fn update_parallax(&self) {
+        let offset = match self.scroll_offset {
+            Some(offset) => offset,
+            None => ScrollMetrics::read().map_or(0.0, |s| s.offset),
+        };
+        let viewport = self.canvas.client_height() as f32;
+        let shift = if viewport > 0.0 {
+            offset / viewport * self.world_height * 2.0 * self.config.scroll_parallax
+        } else {
+            0.0
+        };
+        self.set_uniform_1f(&self.uniforms.scroll, shift);
+    }
+
+    /// Sets the scroll offset in CSS pixels used for parallax. Once called,
+    /// the document scroll position is no longer read automatically, which
+    /// allows feeding the offset of a custom scroll container.
+    pub fn set_scroll_offset(&mut self, offset: f32) {
+        self.scroll_offset = Some(offset);
+    }
+
+    /// Orientation in degrees as reported by `DeviceOrientationEvent`
+    /// (`beta` front-back tilt, `gamma` left-right tilt). Used by the
+    /// `orientation` orbit mode.
+    pub fn set_device_orientation(&mut self, beta: f32, gamma: f32) {
+        // Phones are usually held tilted ~45° towards the viewer
+        self.camera.aim(gamma / 90.0, (beta - 45.0) / 90.0);
     }
 
     pub fn render(&mut self, current_time: f32) -> RenderResult {
@@ -384,20 +804,108 @@ impl SnowfallShader {
             self.fps_last_time = current_time;
         }
 
+        if self.pending_texture.is_some() {
+            self.poll_pending_texture();
+        }
+        if let Some(background) = &mut self.background {
+            if let Err(err) = background.poll(&self.gl) {
+                console::error_1(&err);
+            }
+        }
+        let stale_mask = match (&self.mask, &self.config.mask) {
+            (Some(mask), Some(config)) => mask.is_stale(config),
+            _ => false,
+        };
+        if stale_mask {
+            if let Err(err) = self.update_mask() {
+                console::error_1(&err);
+            }
+        }
+        if self
+            .formation
+            .as_ref()
+            .is_some_and(|f| f.is_finished(self.time))
+        {
+            self.end_formation();
+        }
+        if let Err(err) = self.use_variant() {
+            console::error_1(&err);
+        }
+
         self.time += delta;
         self.update_wind(delta);
 
+        if self.camera.config.orbit == OrbitMode::Scroll {
+            self.track_scroll();
+        }
+        if self.camera.update(delta) {
+            self.update_view();
+        }
+        if self.config.scroll_parallax != 0.0 {
+            self.update_parallax();
+        }
+
         self.set_uniform_1f(&self.uniforms.time, self.time);
-        self.set_uniform_1f(&self.uniforms.wind, self.wind.current);
+        if let Some(sheet) = self.config.sprite_sheet {
+            self.set_uniform_1f(&self.uniforms.sprite_frame, sheet.frame_at(self.time));
+        }
+        if let Some(formation) = &self.formation {
+            let progress = formation.progress(self.time);
+            self.set_uniform_1f(&self.uniforms.formation, progress);
+        }
+        let [wx, wy, wz] = self.wind.drift();
+        self.set_uniform_3f(&self.uniforms.wind, wx, wy, wz);
+        let [vx, vy, vz] = self.wind.drift_rate();
+        self.set_uniform_3f(&self.uniforms.wind_velocity, vx, vy, vz);
+        if let Some(globe) = self.config.globe {
+            self.globe.update(delta, globe.damping);
+            self.update_globe_energy();
+        }
+        if let (Some(emitter), Some(config)) = (&mut self.emitter, &self.config.emitter) {
+            let due = emitter.due(delta, config.rate);
+            self.emit(due);
+        }
 
+        if let Some(post) = &self.post {
+            post.begin(&self.gl);
+        }
         self.gl.clear(GL::COLOR_BUFFER_BIT);
+        if let Some(background) = &self.background {
+            let (width, height) = (self.canvas.width(), self.canvas.height());
+            background.draw(&self.gl, &self.config.background, width, height);
+            self.gl.use_program(Some(&self.program));
+        }
+        if let Some(ground) = &self.ground {
+            let (width, height) = (self.canvas.width() as f32, self.canvas.height() as f32);
+            let aspect = if height > 0.0 { width / height } else { 1.0 };
+            let (camera, floor) = (&self.camera, self.floor());
+            ground.draw(&self.gl, &self.config, camera, aspect, floor, self.time);
+            self.gl.use_program(Some(&self.program));
+        }
         self.gl
             .draw_arrays(GL::POINTS, 0, self.actual_particle_count);
+        if let (Some(glass), Some(globe)) = (&self.glass, &self.config.globe) {
+            let (width, height) = (self.canvas.width() as f32, self.canvas.height() as f32);
+            let aspect = if height > 0.0 { width / height } else { 1.0 };
+            glass.draw(&self.gl, globe, &self.camera, aspect);
+            self.apply_blend();
+            self.gl.use_program(Some(&self.program));
+        }
+        if let Some(post) = &self.post {
+            post.finish(&self.gl, &self.config.postprocess);
+            self.gl.use_program(Some(&self.program));
+        }
 
         RenderResult {
             fps: self.current_fps,
             time: self.time,
             wind: self.wind.current,
+            wind_x: wx,
+            wind_y: wy,
+            wind_z: wz,
+            wind_angle: self.wind.angle(),
+            wind_elevation: self.wind.elevation(),
+            wind_strength: self.wind.magnitude(),
             particle_count: self.actual_particle_count as u32,
         }
     }
@@ -413,6 +921,428 @@ impl SnowfallShader {
         }
     }
 
+    fn update_wind_direction(&mut self) {
+        self.wind.configure(&self.config);
+        let [x, y, z] = self.wind.direction;
+        let s = self.wind.strength;
+        self.set_uniform_3f(&self.uniforms.wind_direction, x * s, y * s, z * s);
+    }
+
+    fn update_atlas_grid(&self) {
+        let layout = AtlasLayout::for_count(self.shape_weights.len());
+        self.set_uniform_2f(
+            &self.uniforms.atlas_grid,
+            layout.columns as f32,
+            layout.rows as f32,
+        );
+
+        // Atlas cells can't be animated, so a sheet only applies to single textures
+        let (frames, rows) = match self.config.sprite_sheet {
+            Some(sheet) if self.shape_weights.is_empty() => (sheet.frames, sheet.rows),
+            _ => (1, 1),
+        };
+        self.set_uniform_2f(&self.uniforms.sprite_grid, frames as f32, rows as f32);
+    }
+
+    /// Creates, resizes or drops the post-processing chain to match
+    /// `config.postprocess`.
+    fn update_post(&mut self) -> Result<(), JsValue> {
+        let config = self.config.postprocess;
+        let (width, height) = (self.canvas.width(), self.canvas.height());
+        match (&mut self.post, config.is_enabled()) {
+            (Some(post), true) => post.resize(&self.gl, width, height, config.scale)?,
+            (None, true) => {
+                self.post = Some(PostProcessor::new(&self.gl, width, height, config.scale)?);
+            }
+            (Some(post), false) => {
+                post.delete(&self.gl);
+                self.post = None;
+            }
+            (None, false) => {}
+        }
+        Ok(())
+    }
+
+    /// Creates or drops the background layer to match `config.background`
+    /// and uploads its image.
+    fn update_background(&mut self) -> Result<(), JsValue> {
+        if !self.config.background.is_visible() {
+            if let Some(layer) = self.background.take() {
+                layer.delete(&self.gl);
+            }
+            return Ok(());
+        }
+        let layer = match &mut self.background {
+            Some(layer) => layer,
+            None => self.background.insert(BackgroundLayer::new(&self.gl)?),
+        };
+        layer.set_image(&self.gl, self.config.background.image.as_ref())
+    }
+
+    /// Creates or drops the ground layer to match `config.ground`.
+    fn update_ground(&mut self) -> Result<(), JsValue> {
+        match (&self.ground, self.config.ground.is_some()) {
+            (None, true) => self.ground = Some(GroundLayer::new(&self.gl, self.time)?),
+            (Some(layer), false) => {
+                layer.delete(&self.gl);
+                self.ground = None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Creates or drops the glass layer to match `config.globe`.
+    fn update_glass(&mut self) -> Result<(), JsValue> {
+        match (&self.glass, self.config.globe.is_some()) {
+            (None, true) => self.glass = Some(GlassLayer::new(&self.gl)?),
+            (Some(layer), false) => {
+                layer.delete(&self.gl);
+                self.glass = None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn update_globe_energy(&self) {
+        self.set_uniform_1f(&self.uniforms.globe_energy, self.globe.energy);
+        self.set_uniform_1f(&self.uniforms.globe_swirl, self.globe.swirl);
+    }
+
+    /// Stirs up the snow globe; 1 lifts every flake. Repeated shakes add
+    /// up. Does nothing without `globe` in the config.
+    pub fn shake(&mut self, strength: f32) {
+        if self.config.globe.is_some() {
+            self.globe.shake(strength);
+        }
+    }
+
+    /// Feeds `DeviceMotionEvent.accelerationIncludingGravity` in m/s².
+    /// Sudden changes shake the snow globe.
+    pub fn set_device_motion(&mut self, x: f32, y: f32, z: f32) {
+        if self.config.globe.is_some() {
+            self.globe.motion([x, y, z]);
+        }
+    }
+
+    /// Redraws the occlusion mask. Element regions and scrolling are
+    /// tracked automatically; call this after other layout changes move
+    /// masked elements.
+    pub fn update_mask(&mut self) -> Result<(), JsValue> {
+        let Some(config) = &self.config.mask else {
+            if let Some(mask) = self.mask.take() {
+                mask.delete(&self.gl);
+            }
+            return Ok(());
+        };
+        let mask = match &mut self.mask {
+            Some(mask) => mask,
+            None => self.mask.insert(OcclusionMask::new(&self.gl)?),
+        };
+        mask.update(&self.gl, config, &self.canvas)?;
+        self.update_mask_uniforms();
+        Ok(())
+    }
+
+    fn update_mask_uniforms(&self) {
+        if let Some(l) = &self.uniforms.mask {
+            self.gl.uniform1i(Some(l), MASK_TEXTURE_UNIT as i32);
+        }
+        let (width, height) = (self.canvas.width().max(1), self.canvas.height().max(1));
+        self.set_uniform_2f(
+            &self.uniforms.mask_scale,
+            1.0 / width as f32,
+            1.0 / height as f32,
+        );
+    }
+
+    /// Gathers flakes into `text`, one line per `\n`. Takes
+    /// `{ count, size, distance, gather, hold, release, font }` with `font`
+    /// in CSS syntax and times in seconds; `hold: 0` keeps the shape until
+    /// `release_formation`. Replaces the current formation. With an
+    /// emitter, only flakes in the air take part.
+    pub fn form_text(&mut self, text: &str, options: JsValue) -> Result<(), JsValue> {
+        let options = FormationOptions::from_js(&options);
+        let shape = Silhouette::from_text(text, &options.font)?;
+        self.start_formation(&shape, &options)
+    }
+
+    /// Gathers flakes into the opaque pixels of a loaded image or other
+    /// texture source, such as a logo. Takes the same options as
+    /// `form_text`.
+    pub fn form_image(&mut self, image: JsValue, options: JsValue) -> Result<(), JsValue> {
+        let image = TextureSource::from_js(&image).ok_or("Unsupported formation image")?;
+        let options = FormationOptions::from_js(&options);
+        let shape = Silhouette::from_image(&image)?;
+        self.start_formation(&shape, &options)
+    }
+
+    /// Lets the flakes of the current formation fall again.
+    pub fn release_formation(&mut self) {
+        if let Some(formation) = &mut self.formation {
+            formation.release(self.time);
+        }
+    }
+
+    fn start_formation(
+        &mut self,
+        shape: &Silhouette,
+        options: &FormationOptions,
+    ) -> Result<(), JsValue> {
+        self.end_formation();
+        let (width, height) = (self.canvas.width() as f32, self.canvas.height() as f32);
+        let aspect = if height > 0.0 { width / height } else { 1.0 };
+        let particles = self.actual_particle_count as usize;
+        let targets = shape.targets(options, &self.camera, aspect, particles);
+        self.formation = Some(Formation::new(&self.gl, &targets, options, self.time)?);
+        Ok(())
+    }
+
+    fn end_formation(&mut self) {
+        if let Some(formation) = self.formation.take() {
+            formation.delete(&self.gl);
+        }
+    }
+
+    /// Respawns `count` flakes from the emitter at the current time.
+    fn emit(&mut self, count: usize) {
+        let (Some(emitter), Some(config), Some(buffers)) =
+            (&mut self.emitter, &self.config.emitter, &self.buffers)
+        else {
+            return;
+        };
+        let space = SpawnSpace {
+            camera: &self.camera,
+            canvas: &self.canvas,
+            depth: self.world_depth,
+            time: self.time,
+            wind: self.wind.drift(),
+            cursor: self.cursor,
+        };
+        emitter.spawn(&self.gl, &buffers.position, config, &space, count);
+    }
+
+    /// Spawns `count` flakes at once from the emitter, for example on a
+    /// click. Does nothing without `emitter` in the config.
+    pub fn burst(&mut self, count: u32) {
+        self.emit(count as usize);
+    }
+
+    /// Pointer position in CSS pixels relative to the canvas, followed by
+    /// the `cursor` emitter.
+    pub fn set_cursor_position(&mut self, x: f32, y: f32) {
+        self.cursor = Some([x, y]);
+    }
+
+    /// World Y of the ground that hail bounces on, kept on screen where the
+    /// camera looks into the flakes.
+    fn floor(&self) -> f32 {
+        self.camera.floor(FLOOR_LEVEL).max(-self.world_height)
+    }
+
+    fn apply_blend(&self) {
+        match self.config.appearance().blend {
+            BlendMode::Normal => self.gl.blend_func(GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Additive => self.gl.blend_func(GL::SRC_ALPHA, GL::ONE),
+        }
+    }
+
+    fn update_fog(&self) {
+        let fog = &self.config.fog;
+        if let Some(l) = &self.uniforms.fog_mode {
+            self.gl.uniform1i(Some(l), fog.mode as i32);
+        }
+        self.set_uniform_3f(&self.uniforms.fog_params, fog.near, fog.far, fog.density);
+        let [r, g, b] = fog.color;
+        self.set_uniform_3f(&self.uniforms.fog_color, r, g, b);
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_precipitation(&mut self, precipitation: Precipitation) -> Result<(), JsValue> {
+        self.config.apply_precipitation(precipitation);
+        self.setup_buffers()
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_theme(&mut self, theme: Theme) -> Result<(), JsValue> {
+        self.config.apply_theme(theme);
+        self.apply_blend();
+        self.setup_buffers()
+    }
+
+    /// Accepts the same values as the `palette` config option.
+    #[cfg(feature = "configurable")]
+    pub fn set_palette(&mut self, value: JsValue) -> Result<(), JsValue> {
+        self.config.palette = Some(Palette::from_js(&value).ok_or("Invalid palette")?);
+        self.setup_buffers()
+    }
+
+    /// Reverts to the theme palette.
+    #[cfg(feature = "configurable")]
+    pub fn clear_palette(&mut self) -> Result<(), JsValue> {
+        self.config.palette = None;
+        self.setup_buffers()
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_shape(&mut self, shape: Shape) {
+        self.config.shape = shape;
+        if let Some(l) = &self.uniforms.shape {
+            self.gl.uniform1i(Some(l), shape as i32);
+        }
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_tumble(&mut self, value: f32) {
+        self.config.tumble = value;
+        self.set_uniform_1f(&self.uniforms.tumble, value);
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_twinkle(&mut self, value: f32) {
+        self.config.twinkle = value;
+        self.set_uniform_1f(&self.uniforms.twinkle, value);
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_sparkle(&mut self, value: f32) {
+        self.config.sparkle = value;
+        self.set_uniform_1f(&self.uniforms.sparkle, value);
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_bloom(&mut self, intensity: f32, threshold: f32) -> Result<(), JsValue> {
+        self.config.postprocess.bloom = intensity.max(0.0);
+        self.config.postprocess.threshold = threshold.clamp(0.0, 1.0);
+        self.update_post()
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_bloom_radius(&mut self, radius: f32) {
+        self.config.postprocess.radius = radius.max(0.0);
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_vignette(&mut self, value: f32) -> Result<(), JsValue> {
+        self.config.postprocess.vignette = value.clamp(0.0, 1.0);
+        self.update_post()
+    }
+
+    /// Resolution of the bloom buffers relative to the canvas. Lower values
+    /// are cheaper and spread the glow wider.
+    #[cfg(feature = "configurable")]
+    pub fn set_post_scale(&mut self, scale: f32) -> Result<(), JsValue> {
+        self.config.postprocess.scale = scale.clamp(POST_MIN_SCALE, 1.0);
+        self.update_post()
+    }
+
+    /// Takes the same values as the `background` config option.
+    #[cfg(feature = "configurable")]
+    pub fn set_background(&mut self, value: JsValue) -> Result<(), JsValue> {
+        self.config.background = Background::from_js(&value);
+        self.update_background()
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_background_color(&mut self, r: f32, g: f32, b: f32) -> Result<(), JsValue> {
+        self.config.background.gradient = Some(([r, g, b], [r, g, b]));
+        if self.background.is_none() {
+            self.update_background()?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_background_fit(&mut self, fit: BackgroundFit) {
+        self.config.background.fit = fit;
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn clear_background(&mut self) {
+        self.config.background = Background::default();
+        if let Some(layer) = self.background.take() {
+            layer.delete(&self.gl);
+        }
+    }
+
+    /// Takes the same values as the `ground` config option; `false` removes
+    /// the ground. Restarts the snow cover.
+    #[cfg(feature = "configurable")]
+    pub fn set_ground(&mut self, value: JsValue) -> Result<(), JsValue> {
+        self.config.ground = GroundConfig::from_js(&value);
+        if let Some(layer) = self.ground.take() {
+            layer.delete(&self.gl);
+        }
+        self.update_ground()
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_ground_color(&mut self, r: f32, g: f32, b: f32) {
+        if let Some(ground) = &mut self.config.ground {
+            ground.color = [r, g, b];
+        }
+    }
+
+    /// Takes the same values as the `globe` config option; `false` returns
+    /// to the world box.
+    #[cfg(feature = "configurable")]
+    pub fn set_globe(&mut self, value: JsValue) -> Result<(), JsValue> {
+        self.config.globe = GlobeConfig::from_js(&value);
+        self.update_glass()?;
+        if self.config.emitter.is_some() {
+            // The globe replaces the emitter pool with the box layout
+            return self.setup_buffers();
+        }
+        self.update_particle_uniforms();
+        Ok(())
+    }
+
+    /// Takes the same values as the `mask` config option.
+    #[cfg(feature = "configurable")]
+    pub fn set_mask(&mut self, value: JsValue) -> Result<(), JsValue> {
+        self.config.mask = MaskConfig::from_js(&value);
+        let result = self.update_mask();
+        if result.is_err() {
+            self.config.mask = None;
+        }
+        result
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn clear_mask(&mut self) -> Result<(), JsValue> {
+        self.config.mask = None;
+        self.update_mask()
+    }
+
+    /// Takes the same values as the `emitter` config option. Restarts the
+    /// pool, so flakes in the air vanish.
+    #[cfg(feature = "configurable")]
+    pub fn set_emitter(&mut self, value: JsValue) -> Result<(), JsValue> {
+        self.config.emitter = EmitterConfig::from_js(&value);
+        self.setup_buffers()
+    }
+
+    /// Returns to filling the world box.
+    #[cfg(feature = "configurable")]
+    pub fn clear_emitter(&mut self) -> Result<(), JsValue> {
+        self.config.emitter = None;
+        self.setup_buffers()
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_emitter_rate(&mut self, rate: f32) {
+        if let Some(emitter) = &mut self.config.emitter {
+            emitter.rate = rate.max(0.0);
+        }
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_blend(&mut self, blend: BlendMode) {
+        self.config.blend = blend;
+        self.apply_blend();
+    }
+
     #[cfg(feature = "configurable")]
     pub fn set_particle_count(&mut self, count: u32) -> Result<(), JsValue> {
         self.base_particle_count = count;
@@ -428,6 +1358,8 @@ impl SnowfallShader {
     #[cfg(feature = "configurable")]
     pub fn set_depth(&mut self, value: f32) -> Result<(), JsValue> {
         self.config.depth = value;
+        self.camera.set_depth(value);
+        self.update_view();
         self.setup_buffers()
     }
 
@@ -456,9 +1388,9 @@ impl SnowfallShader {
     }
 
     #[cfg(feature = "configurable")]
-    pub fn set_direction_x(&mut self, value: f32) -> Result<(), JsValue> {
+    pub fn set_direction_x(&mut self, value: f32) {
         self.config.direction_x = value;
-        self.setup_buffers()
+        self.update_wind_direction();
     }
 
     #[cfg(feature = "configurable")]
@@ -473,23 +1405,295 @@ impl SnowfallShader {
         self.set_uniform_1f(&self.uniforms.rotation_speed, value);
     }
 
+    #[cfg(feature = "configurable")]
+    pub fn set_wind_angle(&mut self, degrees: f32) {
+        self.config.wind_angle = degrees;
+        self.update_wind_direction();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_wind_elevation(&mut self, degrees: f32) {
+        self.config.wind_elevation = degrees;
+        self.update_wind_direction();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_wind_strength(&mut self, value: f32) {
+        self.config.wind_strength = value;
+        self.update_wind_direction();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_focal_distance(&mut self, value: f32) {
+        self.config.focal_distance = value;
+        self.set_uniform_1f(&self.uniforms.focal_distance, value);
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_aperture(&mut self, value: f32) {
+        self.config.aperture = value;
+        self.set_uniform_1f(&self.uniforms.aperture, value);
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_streak_length(&mut self, value: f32) {
+        self.config.streak_length = value;
+        self.set_uniform_1f(&self.uniforms.streak_length, value);
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_fog_mode(&mut self, mode: FogMode) {
+        self.config.fog.mode = mode;
+        self.update_fog();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_fog_color(&mut self, r: f32, g: f32, b: f32) {
+        self.config.fog.color = [r, g, b];
+        self.update_fog();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_fog_range(&mut self, near: f32, far: f32) {
+        self.config.fog.set_range(near, far);
+        self.update_fog();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_fog_density(&mut self, value: f32) {
+        self.config.fog.density = value;
+        self.update_fog();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_scroll_parallax(&mut self, value: f32) {
+        self.config.scroll_parallax = value;
+        self.update_parallax();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_camera_fov(&mut self, degrees: f32) {
+        self.config.camera.fov = degrees;
+        self.camera.config.fov = degrees;
+        self.update_projection();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_camera_position(&mut self, x: f32, y: f32, z: f32) {
+        self.config.camera.position = [x, y, z];
+        self.camera.config.position = [x, y, z];
+        self.update_view();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_camera_target(&mut self, x: f32, y: f32, z: f32) {
+        self.config.camera.target = Some([x, y, z]);
+        self.camera.set_target([x, y, z]);
+        self.update_view();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_camera_roll(&mut self, degrees: f32) {
+        self.config.camera.roll = degrees;
+        self.camera.config.roll = degrees;
+        self.update_view();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_camera_orthographic(&mut self, enabled: bool) {
+        self.config.camera.orthographic = enabled;
+        self.camera.config.orthographic = enabled;
+        self.update_projection();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn set_camera_orbit(&mut self, mode: OrbitMode) {
+        self.config.camera.orbit = mode;
+        self.camera.config.orbit = mode;
+    }
+
     #[cfg(feature = "configurable")]
     pub fn set_color(&mut self, r: f32, g: f32, b: f32) {
-        self.set_uniform_3f(&self.uniforms.color_tint, r, g, b);
-        if let Some(loc) = &self.uniforms.use_color_tint {
-            self.gl.uniform1i(Some(loc), 1);
-        }
+        self.config.color = Some(vec![r, g, b]);
+        self.update_color();
     }
 
     #[cfg(feature = "configurable")]
     pub fn clear_color(&mut self) {
-        if let Some(loc) = &self.uniforms.use_color_tint {
-            self.gl.uniform1i(Some(loc), 0);
+        self.config.color = None;
+        self.update_color();
+    }
+
+    fn update_color(&self) {
+        if let Some([r, g, b]) = self.config.appearance().tint {
+            self.set_uniform_3f(&self.uniforms.color_tint, r, g, b);
+        }
+    }
+
+    /// Takes an image, `ImageBitmap`, canvas, `OffscreenCanvas`, `ImageData`
+    /// or `{ data, width, height }` with raw RGBA bytes.
+    #[cfg(feature = "configurable")]
+    pub fn set_texture(&mut self, source: JsValue) -> Result<(), JsValue> {
+        let source = TextureSource::from_js(&source).ok_or("Unsupported texture source")?;
+        self.apply_texture(&source)
+    }
+
+    fn apply_texture(&mut self, source: &TextureSource) -> Result<(), JsValue> {
+        if !source.is_ready() {
+            self.pending_texture = Some(PendingTexture::Single(source.clone()));
+            console::log_1(&"[RemnaSnow] Texture is still loading, waiting".into());
+            return Ok(());
+        }
+
+        self.upload_texture(source)?;
+
+        if !self.shape_weights.is_empty() {
+            self.shape_weights.clear();
+            self.update_atlas_grid();
+        }
+
+        let (width, height) = source.size();
+        console::log_1(&format!("[RemnaSnow] Texture loaded: {}x{}", width, height).into());
+
+        Ok(())
+    }
+
+    /// Sets several shapes at once. Takes an array of images or
+    /// `{ image, weight }` objects; each particle picks a shape by weight.
+    #[cfg(feature = "configurable")]
+    pub fn set_texture_atlas(&mut self, textures: JsValue) -> Result<(), JsValue> {
+        let entries = AtlasEntry::parse_list(&textures);
+        if entries.is_empty() {
+            return Err("Texture atlas needs at least one source".into());
+        }
+        self.apply_texture_atlas(&entries)
+    }
+
+    fn apply_texture_atlas(&mut self, entries: &[AtlasEntry]) -> Result<(), JsValue> {
+        if !entries.iter().all(|e| e.source.is_ready()) {
+            self.pending_texture = Some(PendingTexture::Atlas(entries.to_vec()));
+            console::log_1(&"[RemnaSnow] Texture atlas is still loading, waiting".into());
+            return Ok(());
+        }
+
+        let (canvas, layout) = compose_atlas(entries)?;
+        self.upload_texture(&TextureSource::Canvas(canvas))?;
+
+        self.shape_weights = entries.iter().map(|e| e.weight).collect();
+        self.setup_buffers()?;
+
+        console::log_1(
+            &format!(
+                "[RemnaSnow] Texture atlas loaded: {} shapes ({}x{})",
+                entries.len(),
+                layout.columns,
+                layout.rows
+            )
+            .into(),
+        );
+        Ok(())
+    }
+
+    /// Generates `count` unique snowflake crystals from `seed` and uses them
+    /// as an equally weighted atlas.
+    #[cfg(feature = "configurable")]
+    pub fn set_procedural_texture(&mut self, seed: u32, count: u32) -> Result<(), JsValue> {
+        self.apply_procedural_texture(ProceduralConfig {
+            seed,
+            count: count.clamp(1, PROCEDURAL_MAX_COUNT),
+            ..self.config.procedural.unwrap_or_default()
+        })
+    }
+
+    fn apply_procedural_texture(&mut self, config: ProceduralConfig) -> Result<(), JsValue> {
+        let atlas = generate_atlas(config.seed, config.count, config.size);
+        self.upload_texture(&TextureSource::Raw {
+            data: atlas.pixels,
+            width: atlas.width,
+            height: atlas.height,
+        })?;
+
+        self.config.procedural = Some(config);
+        self.shape_weights = vec![1.0; config.count as usize];
+        self.setup_buffers()?;
+
+        console::log_1(
+            &format!(
+                "[RemnaSnow] Procedural crystals generated: {} (seed {})",
+                config.count, config.seed
+            )
+            .into(),
+        );
+        Ok(())
+    }
+
+    /// Replaces the `displace` and `shade` shader functions. Takes
+    /// `{ displace, shade }` GLSL strings; on compile errors the current
+    /// shaders are kept and the error lines refer to the snippet.
+    #[cfg(feature = "configurable")]
+    pub fn set_shader_hooks(&mut self, hooks: JsValue) -> Result<(), JsValue> {
+        self.apply_shader_hooks(ShaderHooks::from_js(&hooks))
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn clear_shader_hooks(&mut self) -> Result<(), JsValue> {
+        self.apply_shader_hooks(ShaderHooks::default())
+    }
+
+    #[cfg(feature = "configurable")]
+    fn apply_shader_hooks(&mut self, hooks: ShaderHooks) -> Result<(), JsValue> {
+        let program = Self::create_program(&self.gl, &hooks, self.features)?;
+        self.gl.use_program(Some(&program));
+        // Other variants are rebuilt with the new hooks when needed
+        for (_, old) in self.programs.drain() {
+            self.gl.delete_program(Some(&old));
         }
+        self.failed_features = None;
+        self.programs.insert(self.features, program.clone());
+        self.uniforms = Uniforms::load(&self.gl, &program);
+        self.program = program;
+        self.config.shader_hooks = hooks;
+        self.sync_uniforms();
+        Ok(())
     }
 
+    /// Animates the texture as `frames` × `rows` frames played at `fps`,
+    /// each particle starting at a random frame.
     #[cfg(feature = "configurable")]
-    pub fn set_texture(&mut self, image: &HtmlImageElement) -> Result<(), JsValue> {
+    pub fn set_sprite_sheet(&mut self, frames: u32, rows: u32, fps: f32) {
+        self.config.sprite_sheet = Some(SpriteSheet::new(frames, rows, fps));
+        self.update_atlas_grid();
+    }
+
+    #[cfg(feature = "configurable")]
+    pub fn clear_sprite_sheet(&mut self) {
+        self.config.sprite_sheet = None;
+        self.update_atlas_grid();
+    }
+
+    /// Changes how textures are uploaded and re-uploads the current one.
+    /// Takes `{ mipmaps, premultiplyAlpha, flipY, size, anisotropy }`.
+    #[cfg(feature = "configurable")]
+    pub fn set_texture_options(&mut self, options: JsValue) -> Result<(), JsValue> {
+        self.config.texture_options = TextureOptions::from_js(&options);
+        if let Some(source) = self.texture_source.clone() {
+            self.upload_texture(&source)?;
+        }
+        Ok(())
+    }
+
+    /// Uploads a ready source into the texture, creating it if needed, and
+    /// binds it to unit 0 for sampling.
+    fn upload_texture(&mut self, source: &TextureSource) -> Result<(), JsValue> {
+        let options = self.config.texture_options;
+        let (width, height) = source.size();
+        let (upload_width, upload_height) = options.upload_size(width, height);
+        let resized = if (upload_width, upload_height) != (width, height) {
+            Some(source.resized(upload_width, upload_height)?)
+        } else {
+            None
+        };
+
         if self.texture.is_none() {
             self.texture = self.gl.create_texture();
         }
@@ -497,56 +1701,110 @@ impl SnowfallShader {
         let texture = self.texture.as_ref().ok_or("Failed to create texture")?;
 
         self.gl.bind_texture(GL::TEXTURE_2D, Some(texture));
-        self.gl.tex_image_2d_with_u32_and_u32_and_image(
-            GL::TEXTURE_2D,
-            0,
-            GL::RGBA as i32,
-            GL::RGBA,
-            GL::UNSIGNED_BYTE,
-            image,
-        )?;
+        self.gl
+            .pixel_storei(GL::UNPACK_FLIP_Y_WEBGL, options.flip_y as i32);
+        self.gl.pixel_storei(
+            GL::UNPACK_PREMULTIPLY_ALPHA_WEBGL,
+            options.premultiply_alpha as i32,
+        );
+        resized.as_ref().unwrap_or(source).upload(&self.gl)?;
 
         self.gl
             .tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE as i32);
         self.gl
             .tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE as i32);
+        let min_filter = if options.mipmaps {
+            self.gl.generate_mipmap(GL::TEXTURE_2D);
+            GL::LINEAR_MIPMAP_LINEAR
+        } else {
+            GL::LINEAR
+        };
         self.gl
-            .tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::LINEAR as i32);
+            .tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, min_filter as i32);
         self.gl
             .tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MAG_FILTER, GL::LINEAR as i32);
+        self.apply_anisotropy(options.anisotropy);
 
         self.gl.active_texture(GL::TEXTURE0);
         self.gl.bind_texture(GL::TEXTURE_2D, Some(texture));
+        self.update_texture_uniforms();
 
+        self.texture_source = Some(source.clone());
+        self.pending_texture = None;
+        Ok(())
+    }
+
+    fn update_texture_uniforms(&self) {
         if let Some(loc) = &self.uniforms.texture {
             self.gl.uniform1i(Some(loc), 0);
         }
-
-        if let Some(loc) = &self.uniforms.use_texture {
-            self.gl.uniform1i(Some(loc), 1);
+        if let Some(loc) = &self.uniforms.premultiplied {
+            let premultiplied = self.config.texture_options.premultiply_alpha;
+            self.gl.uniform1i(Some(loc), premultiplied as i32);
         }
+    }
 
-        console::log_1(
-            &format!(
-                "[RemnaSnow] Texture loaded: {}x{}",
-                image.natural_width(),
-                image.natural_height()
-            )
-            .into(),
+    /// Sets anisotropic filtering on the bound texture when the extension
+    /// is available.
+    fn apply_anisotropy(&self, level: f32) {
+        if self
+            .gl
+            .get_extension("EXT_texture_filter_anisotropic")
+            .ok()
+            .flatten()
+            .is_none()
+        {
+            return;
+        }
+        let max = self
+            .gl
+            .get_parameter(MAX_TEXTURE_MAX_ANISOTROPY_EXT)
+            .ok()
+            .and_then(|v| v.as_f64())
+            .unwrap_or(1.0) as f32;
+        self.gl.tex_parameterf(
+            GL::TEXTURE_2D,
+            TEXTURE_MAX_ANISOTROPY_EXT,
+            level.clamp(1.0, max.max(1.0)),
         );
+    }
 
-        Ok(())
+    /// Uploads a texture that was set before its image finished loading.
+    fn poll_pending_texture(&mut self) {
+        let ready = match &self.pending_texture {
+            Some(PendingTexture::Single(source)) => source.is_ready(),
+            Some(PendingTexture::Atlas(entries)) => entries.iter().all(|e| e.source.is_ready()),
+            None => false,
+        };
+        if !ready {
+            return;
+        }
+
+        let result = match self.pending_texture.take() {
+            Some(PendingTexture::Single(source)) => self.apply_texture(&source),
+            Some(PendingTexture::Atlas(entries)) => self.apply_texture_atlas(&entries),
+            None => Ok(()),
+        };
+        if let Err(err) = result {
+            console::error_1(&err);
+        }
     }
 
+    /// Drops the texture, atlas or procedural crystals and reverts to the
+    /// default sprite.
     #[cfg(feature = "configurable")]
     pub fn clear_texture(&mut self) {
         if let Some(texture) = self.texture.take() {
             self.gl.delete_texture(Some(&texture));
         }
-
-        if let Some(loc) = &self.uniforms.use_texture {
-            self.gl.uniform1i(Some(loc), 0);
-        }
+        self.texture_source = None;
+        self.pending_texture = None;
+        self.config.texture = None;
+        self.config.textures.clear();
+        self.config.procedural = None;
+        self.shape_weights.clear();
+        self.update_atlas_grid();
+        self.update_texture_uniforms();
 
         console::log_1(&"[RemnaSnow] Texture cleared".into());
     }
@@ -560,6 +1818,9 @@ impl SnowfallShader {
     pub fn get_wind(&self) -> f32 {
         self.wind.current
     }
+    pub fn get_wind_vector(&self) -> Vec<f32> {
+        self.wind.velocity().to_vec()
+    }
     pub fn get_particle_count(&self) -> u32 {
         self.actual_particle_count as u32
     }
@@ -1,26 +1,191 @@
 //! GLSL shaders
+//!
+//! Optional stages are wrapped in `#ifdef`s and compiled into separate
+//! program variants, see `ShaderFeatures`.
+
+/// Replaced by the `displace` and `shade` hook functions.
+pub const HOOK_MARKER: &str = "// @hooks";
+pub const DEFAULT_DISPLACE: &str = "vec3 displace(vec3 pos, float t) { return pos; }";
+pub const DEFAULT_SHADE: &str = "vec4 shade(vec4 color, vec2 uv) { return color; }";
+
+/// Set of optional shader stages, each enabled by a `#define`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ShaderFeatures(u32);
+
+impl ShaderFeatures {
+    /// Samples `u_texture` instead of drawing a procedural shape.
+    pub const TEXTURE: Self = Self(1);
+    pub const COLOR_TINT: Self = Self(1 << 1);
+    pub const FOG: Self = Self(1 << 2);
+    pub const DEPTH_OF_FIELD: Self = Self(1 << 3);
+    pub const STREAKS: Self = Self(1 << 4);
+    pub const TUMBLE: Self = Self(1 << 5);
+    /// Twinkle and sparkle.
+    pub const SPARKLE: Self = Self(1 << 6);
+    /// Hides flakes below the floor.
+    pub const GROUND: Self = Self(1 << 7);
+    /// Confines flakes to a snow globe instead of the world box.
+    pub const GLOBE: Self = Self(1 << 8);
+    /// Fades flakes out over masked screen areas.
+    pub const MASK: Self = Self(1 << 9);
+    /// Pulls flakes towards `a_target` to form a shape.
+    pub const FORMATION: Self = Self(1 << 10);
+    /// Spawns flakes from an emitter pool instead of the world box.
+    pub const EMITTER: Self = Self(1 << 11);
+
+    const DEFINES: [(Self, &'static str); 12] = [
+        (Self::TEXTURE, "TEXTURE"),
+        (Self::COLOR_TINT, "COLOR_TINT"),
+        (Self::FOG, "FOG"),
+        (Self::DEPTH_OF_FIELD, "DEPTH_OF_FIELD"),
+        (Self::STREAKS, "STREAKS"),
+        (Self::TUMBLE, "TUMBLE"),
+        (Self::SPARKLE, "SPARKLE"),
+        (Self::GROUND, "GROUND"),
+        (Self::GLOBE, "GLOBE"),
+        (Self::MASK, "MASK"),
+        (Self::FORMATION, "FORMATION"),
+        (Self::EMITTER, "EMITTER"),
+    ];
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn set(&mut self, other: Self, enabled: bool) {
+        if enabled {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+
+    /// `base` with a `#define` line for every enabled feature.
+    pub fn source(self, base: &str) -> String {
+        let mut source = String::new();
+        for (feature, name) in Self::DEFINES {
+            if self.contains(feature) {
+                source.push_str("#define ");
+                source.push_str(name);
+                source.push('\n');
+            }
+        }
+        source + base
+    }
+}
 
 pub const VERTEX_SHADER_SOURCE: &str = r#"
     precision highp float;
 
+#ifdef EMITTER
+    attribute vec4 a_spawn;          // xyz: spawn position, w: spawn time
+    uniform float u_emitterLifetime; // seconds until a flake vanishes
+    uniform float u_emitterSpread;   // outward start speed
+    #define a_position a_spawn.xyz
+#else
     attribute vec3 a_position;
+#endif
     attribute vec4 a_color;
     attribute vec3 a_rotation;
     attribute vec3 a_speed;
     attribute float a_size;
+    attribute vec4 a_variation;      // x: streak weight, y: atlas shape index, z: animation phase, w: twinkle rate
 
     uniform float u_time;
     uniform mat4 u_projection;
+    uniform mat4 u_view;
     uniform vec3 u_worldSize;
     uniform float u_gravity;
-    uniform float u_wind;
+    uniform vec3 u_wind;             // accumulated gust drift
+    uniform vec3 u_windDirection;    // base drift, scaled by wind strength
     uniform float u_pointScale;      // precomputed: resolution.y * 0.015
     uniform float u_rotationSpeed;
+    uniform float u_scroll;          // scroll offset in world units
+    uniform float u_bounce;          // hop height on the floor, 0 wraps straight through
+    uniform float u_floor;           // world Y of the floor
 
     // Use lower-precision varyings where suitable to save bandwidth on mobile GPUs
     varying lowp vec4 v_color;
     varying mediump vec2 v_rotSinCos;  // Pre-computed sin/cos for rotation
 
+#ifdef TEXTURE
+    uniform mediump vec2 u_atlasGrid; // atlas columns, rows
+    varying mediump vec2 v_cell;       // atlas cell column, row
+    varying mediump float v_phase;     // sprite sheet offset, 0-1 of the loop
+#endif
+
+#ifdef DEPTH_OF_FIELD
+    uniform float u_focalDistance;
+    uniform float u_aperture;
+    varying lowp float v_blur;         // circle of confusion, 0 = in focus
+#endif
+
+#ifdef STREAKS
+    uniform vec3 u_windVelocity;     // rate of change of u_wind
+    uniform float u_streakLength;    // seconds of motion smeared
+    uniform vec2 u_resolution;
+    varying mediump vec3 v_streak;     // xy: direction in point space, z: share of streak in sprite
+#endif
+
+#ifdef TUMBLE
+    uniform float u_tumble;          // flip speed around the sprite axes
+    varying mediump vec2 v_tumble;     // cosines of the flips around the sprite Y and X axes
+#endif
+
+#ifdef SPARKLE
+    uniform float u_twinkle;         // brightness pulse depth
+    uniform float u_sparkle;         // facet glint strength
+    varying lowp vec2 v_sparkle;       // x: brightness, y: glint
+#endif
+
+#ifdef FOG
+    uniform int u_fogMode;           // 1 linear, 2 exp, 3 exp2
+    uniform vec3 u_fogParams;        // near, far, density
+    varying lowp float v_fog;          // 0 = clear, 1 = fully fogged
+
+    float fogFactor(float depth) {
+        if (u_fogMode == 1) {
+            return clamp((depth - u_fogParams.x) / (u_fogParams.y - u_fogParams.x), 0.0, 1.0);
+        }
+        float d = u_fogParams.z * max(depth - u_fogParams.x, 0.0);
+        if (u_fogMode == 2) {
+            return 1.0 - exp(-d);
+        }
+        if (u_fogMode == 3) {
+            return 1.0 - exp(-d * d);
+        }
+        return 0.0;
+    }
+#endif
+
+#ifdef GLOBE
+    uniform vec4 u_globe;            // center, radius
+    uniform float u_globeEnergy;     // shake energy, 0 = settled
+    uniform float u_globeSwirl;      // swirl angle accumulated from the energy
+
+    vec3 globePosition(vec3 flutter) {
+        // Spawn box coordinates double as per-flake random numbers
+        vec3 r = a_position / u_worldSize * 0.5 + 0.5;
+        // Light flakes stay up longer than heavy ones
+        float lift = smoothstep(0.0, 1.0, u_globeEnergy * (0.6 + a_speed.z * 0.5));
+        float angle = r.x * 6.28318 + u_globeSwirl * (0.6 + r.z * 0.8);
+        float radius = sqrt(r.z) * 0.92;
+        float height = sqrt(1.0 - radius * radius);
+        // Settled flakes pile up on the bottom of the glass
+        float restY = -height + r.y * 0.15;
+        float cloudY = (r.y * 2.0 - 1.0) * height * 0.9;
+        vec3 p = vec3(cos(angle) * radius, mix(restY, cloudY, lift), sin(angle) * radius);
+        return u_globe.xyz + p * u_globe.w + flutter * lift;
+    }
+#endif
+
+#ifdef FORMATION
+    attribute vec4 a_target;         // xyz: place in the shape, w: start delay 0-1, negative to stay out
+    uniform float u_formation;       // 0 = falling freely, 1 = shape complete
+#endif
+
+    // @hooks
+
     void main() {
         mediump float t = u_time;
         mediump float rot_factor = t * a_rotation.y * u_rotationSpeed * 0.1;
@@ -31,64 +196,593 @@ pub const VERTEX_SHADER_SOURCE: &str = r#"
         mediump float s = sin(oscillation);
         mediump float c = cos(oscillation);
 
+#ifdef GLOBE
+        vec3 pos = globePosition(vec3(s, 0.0, c) * swing * 0.1);
+#else
+#ifdef EMITTER
+        // Pooled flakes wait for their spawn time and vanish when they expire
+        float age = u_time - a_spawn.w;
+        if (age < 0.0 || age > u_emitterLifetime) {
+            gl_Position = vec4(0.0, 0.0, 2.0, 1.0);
+            gl_PointSize = 0.0;
+            return;
+        }
+#else
+        float age = t;
+#endif
         vec3 pos = a_position;
 
-        pos.x = mod(pos.x + t * 0.5 + u_wind * a_speed.x, u_worldSize.x * 2.0) - u_worldSize.x;
-        pos.y = mod(pos.y - t * a_speed.y * u_gravity, u_worldSize.y * 2.0) - u_worldSize.y;
+        pos += u_windDirection * (age * 0.5) + u_wind * a_speed.x;
+        pos.y -= age * a_speed.y * u_gravity;
+
+#ifdef EMITTER
+        // Random outward push, slowed down by drag
+        vec2 spread = vec2(cos(a_rotation.x), sin(a_rotation.x)) * (0.5 + a_speed.z * 0.25);
+        pos.xy += spread * u_emitterSpread * (1.0 - exp(-age * 2.0)) * 0.5;
+#else
+        // Parallax: nearest flakes (z = depth) follow the scroll fully,
+        // the farthest ones (z = -depth) at a quarter of the speed
+        float nearness = clamp((a_position.z + u_worldSize.z) / (2.0 * u_worldSize.z), 0.0, 1.0);
+        pos.y += u_scroll * (0.25 + 0.75 * nearness);
+
+#ifdef GROUND
+        // Flakes land on the ground and restart at the top instead of falling
+        // on through it. Bouncing flakes need the fall past the floor for
+        // their hops, which keep them above it.
+        float bottom = u_bounce > 0.0 ? -u_worldSize.y : u_floor;
+        pos.xz = mod(pos.xz + u_worldSize.xz, u_worldSize.xz * 2.0) - u_worldSize.xz;
+        pos.y = bottom + mod(pos.y - bottom, u_worldSize.y - bottom);
+#else
+        pos = mod(pos + u_worldSize, u_worldSize * 2.0) - u_worldSize;
+#endif
+#endif
+
+        if (u_bounce > 0.0) {
+            // Distance fallen past the floor turns into decaying hops
+            float below = u_floor - pos.y;
+            if (below > 0.0) {
+                float hop = below / (u_bounce * 2.0);
+                pos.y = u_floor + u_bounce * abs(sin(hop * 3.14159)) * exp(-hop * 0.7);
+            }
+        }
 
         pos.x += s * swing;
         pos.z += c * swing;
+#endif
+#ifdef FORMATION
+        if (a_target.w >= 0.0) {
+            // Flakes set off one after another and keep fluttering in place
+            float gathered = smoothstep(a_target.w * 0.5, a_target.w * 0.5 + 0.5, u_formation);
+            pos = mix(pos, a_target.xyz + vec3(s, c, 0.0) * 0.15, gathered);
+        }
+#endif
+        pos = displace(pos, t);
+
+#ifdef GROUND
+        // Hooks, emitters and formations can still move flakes below the ground
+        if (pos.y < u_floor) {
+            gl_Position = vec4(0.0, 0.0, 2.0, 1.0);
+            gl_PointSize = 0.0;
+            return;
+        }
+#endif
 
-        vec4 projected = u_projection * vec4(pos, 1.0);
+        vec4 viewPos = u_view * vec4(pos, 1.0);
+        vec4 projected = u_projection * viewPos;
         gl_Position = projected;
-        gl_PointSize = a_size * u_pointScale / projected.w;
+
+        float depth = -viewPos.z;
+        float pointSize = a_size * u_pointScale / projected.w;
+#ifdef DEPTH_OF_FIELD
+        // Out-of-focus flakes spread into larger, fainter discs
+        v_blur = clamp(u_aperture * abs(depth - u_focalDistance) / max(depth, 1.0), 0.0, 1.0);
+        pointSize *= 1.0 + v_blur * 1.5;
+#endif
+#ifdef FOG
+        v_fog = fogFactor(depth);
+#endif
+
+#ifdef STREAKS
+        v_streak = vec3(0.0);
+        float streakLength = u_streakLength * a_variation.x;
+        if (streakLength > 0.0) {
+            // Analytic derivative of the motion above
+            float omega = a_speed.z * u_rotationSpeed * 0.3;
+            vec3 velocity = u_windDirection * 0.5 + u_windVelocity * a_speed.x;
+            velocity.y -= a_speed.y * u_gravity;
+            velocity.x += c * omega * swing;
+            velocity.z -= s * omega * swing;
+
+            // Stretch the sprite over the screen-space path of the last
+            // streakLength seconds, centered between head and tail
+            vec3 tail = pos - velocity * streakLength;
+            vec4 projectedTail = u_projection * u_view * vec4(tail, 1.0);
+            vec2 pixels = (projected.xy / projected.w - projectedTail.xy / projectedTail.w)
+                * 0.5 * u_resolution;
+            float len = length(pixels);
+            if (len > 0.5 && projectedTail.w > 0.0) {
+                gl_Position = u_projection * u_view * vec4(mix(pos, tail, 0.5), 1.0);
+                // gl_PointCoord grows downwards
+                v_streak = vec3(vec2(pixels.x, -pixels.y) / len, len / (pointSize + len));
+                pointSize += len;
+            }
+        }
+#endif
+        gl_PointSize = pointSize;
 
         v_color = a_color;
-        
+#ifdef EMITTER
+        // Fade out over the last fifth of the lifetime
+        v_color.a *= clamp((u_emitterLifetime - age) / (u_emitterLifetime * 0.2), 0.0, 1.0);
+#endif
+
+#ifdef TEXTURE
+        v_phase = a_variation.z;
+        float shapeIndex = mod(a_variation.y, u_atlasGrid.x * u_atlasGrid.y);
+        v_cell = vec2(mod(shapeIndex, u_atlasGrid.x), floor(shapeIndex / u_atlasGrid.x));
+#endif
+
         mediump float final_rotation = a_rotation.x + rot_factor;
         v_rotSinCos = vec2(sin(final_rotation), cos(final_rotation));
+
+        vec2 tumble = vec2(1.0);
+#ifdef TUMBLE
+        // Flipping reuses the rotation attributes: phase from the initial
+        // angle, speeds from spin speed and swing amplitude
+        mediump float flip = t * u_tumble;
+        tumble = vec2(
+            cos(a_rotation.x + flip * (0.4 + a_rotation.y * 0.2)),
+            cos(a_rotation.x * 1.7 + flip * (0.3 + a_rotation.z * 0.3))
+        );
+        v_tumble = tumble;
+#endif
+
+#ifdef SPARKLE
+        v_sparkle = vec2(1.0, 0.0);
+        if (u_twinkle > 0.0) {
+            mediump float pulse = 0.5 + 0.5 * sin(t * a_variation.w + a_rotation.x);
+            v_sparkle.x = 1.0 - u_twinkle * (1.0 - pulse);
+        }
+        if (u_sparkle > 0.0) {
+            // Six-fold facets catch the light briefly as the flake turns
+            mediump float facet = abs(cos(final_rotation * 3.0 + a_rotation.z));
+            v_sparkle.y = u_sparkle * pow(facet, 48.0) * abs(tumble.x * tumble.y);
+        }
+#endif
     }
 "#;
 
 pub const FRAGMENT_SHADER_SOURCE: &str = r#"
     precision mediump float;
 
-    uniform sampler2D u_texture;
-    uniform bool u_useTexture;
-    uniform vec3 u_colorTint;  // default (1,1,1)
-    uniform bool u_useColorTint;
+    uniform int u_shape;       // 0 circle, 1 rectangle, 2 petal, 3 leaf, 4 ember
     varying lowp vec4 v_color;
     varying mediump vec2 v_rotSinCos;
 
+#ifdef TEXTURE
+    uniform sampler2D u_texture;
+    uniform bool u_premultiplied;
+    uniform vec2 u_atlasGrid;
+    uniform vec2 u_spriteGrid;   // sprite sheet frames per row, rows
+    uniform float u_spriteFrame; // current frame before the per-particle phase
+    varying mediump vec2 v_cell;
+    varying mediump float v_phase;
+#endif
+
+#ifdef COLOR_TINT
+    uniform vec3 u_colorTint;
+#endif
+
+#ifdef DEPTH_OF_FIELD
+    varying lowp float v_blur;
+#else
+    const float v_blur = 0.0;
+#endif
+
+#ifdef STREAKS
+    varying mediump vec3 v_streak;
+#endif
+
+#ifdef TUMBLE
+    varying mediump vec2 v_tumble;
+#else
+    const vec2 v_tumble = vec2(1.0);
+#endif
+
+#ifdef SPARKLE
+    varying lowp vec2 v_sparkle;
+#endif
+
+#ifdef FOG
+    uniform vec3 u_fogColor;
+    varying lowp float v_fog;
+#endif
+
+#ifdef MASK
+    uniform sampler2D u_mask;    // alpha: share of the flake hidden
+    uniform vec2 u_maskScale;    // 1 / framebuffer size
+#endif
+
+    float fill(float d, float edge) {
+        return 1.0 - smoothstep(-edge, 0.0, d);
+    }
+
+    // @hooks
+
     void main() {
         vec2 coord = gl_PointCoord - 0.5;
 
+#ifdef STREAKS
+        // Collapse the streak segment so the flake shape is drawn along it
+        if (v_streak.z > 0.0) {
+            float h = v_streak.z * 0.5;
+            coord -= clamp(dot(coord, v_streak.xy), -h, h) * v_streak.xy;
+            coord /= 1.0 - v_streak.z;
+        }
+#endif
+
         vec2 rotated = vec2(
             coord.x * v_rotSinCos.y - coord.y * v_rotSinCos.x,
             coord.x * v_rotSinCos.x + coord.y * v_rotSinCos.y
         );
 
-        if (u_useTexture) {
-            vec4 texColor = texture2D(u_texture, rotated + 0.5);
-            if (u_useColorTint) {
-                float intensity = dot(texColor.rgb, vec3(0.299, 0.587, 0.114));
-                gl_FragColor = vec4(u_colorTint * intensity * v_color.rgb, texColor.a * v_color.a);
+        // Flipped sprites are foreshortened along the flip axis
+        rotated /= max(abs(v_tumble), vec2(0.05));
+        float facing = 0.7 + 0.3 * abs(v_tumble.x * v_tumble.y);
+
+        vec4 color;
+#ifdef TEXTURE
+        vec2 uv = rotated + 0.5;
+        vec2 grid = u_atlasGrid;
+        vec2 cell = v_cell;
+        float frames = u_spriteGrid.x * u_spriteGrid.y;
+        if (frames > 1.0) {
+            float frame = mod(floor(u_spriteFrame + v_phase * frames), frames);
+            grid = u_spriteGrid;
+            cell = vec2(mod(frame, grid.x), floor((frame + 0.5) / grid.x));
+        }
+        if (grid.x * grid.y > 1.0) {
+            // Corners of rotated sprites would sample neighbouring cells
+            if (uv.x < 0.0 || uv.y < 0.0 || uv.x > 1.0 || uv.y > 1.0) {
+                discard;
+            }
+            uv = (cell + uv) / grid;
+        }
+        // Bias towards coarser mip levels when out of focus
+        vec4 texColor = texture2D(u_texture, uv, v_blur * 3.0);
+        if (u_premultiplied) {
+            // Filtering is done on premultiplied texels, blending is not
+            texColor.rgb /= max(texColor.a, 0.001);
+        }
+        texColor.a *= 1.0 - v_blur * 0.6;
+#ifdef COLOR_TINT
+        float intensity = dot(texColor.rgb, vec3(0.299, 0.587, 0.114));
+        color = vec4(u_colorTint * intensity * v_color.rgb, texColor.a * v_color.a);
+#else
+        color = vec4(texColor.rgb * v_color.rgb, texColor.a * v_color.a);
+#endif
+#else
+        vec3 rgb = v_color.rgb * facing;
+#ifdef COLOR_TINT
+        rgb *= u_colorTint;
+#endif
+        if (u_shape != 0) {
+            float edge = 0.03 + v_blur * 0.2;
+            float alpha;
+            if (u_shape == 1) {
+                // rectangle
+                vec2 d = abs(rotated) - vec2(0.4, 0.22);
+                alpha = fill(max(d.x, d.y), edge);
+            } else if (u_shape == 2) {
+                // petal: ellipse with a notch at the tip
+                float body = length(rotated / vec2(0.26, 0.46)) - 1.0;
+                float notch = 0.1 - length(rotated - vec2(0.0, -0.48));
+                alpha = fill(max(body * 0.26, notch), edge);
+            } else if (u_shape == 3) {
+                // leaf: intersection of two circles with a darker midrib
+                float d = max(length(rotated - vec2(0.28, 0.0)), length(rotated + vec2(0.28, 0.0))) - 0.46;
+                alpha = fill(d, edge);
+                rgb *= 1.0 - 0.3 * (1.0 - smoothstep(0.0, 0.025, abs(rotated.x)));
             } else {
-                gl_FragColor = vec4(texColor.rgb * v_color.rgb, texColor.a * v_color.a);
+                // ember: soft glow with a hot core
+                float dist_sq = dot(rotated, rotated);
+                alpha = exp(-dist_sq * 18.0);
+                rgb = mix(rgb, vec3(1.0, 0.95, 0.8), exp(-dist_sq * 60.0));
             }
-            return;
+            alpha /= 1.0 + v_blur * 2.0;
+            color = vec4(rgb, alpha * v_color.a);
+        } else {
+            // default (circle)
+            float dist_sq = dot(rotated, rotated);
+
+            // thresholds: radius 0.25 and 0.5 squared = 0.0625, 0.25
+            // Blur moves the inner edge to the center and spreads the energy
+            float alpha = 1.0 - smoothstep(0.0625 * (1.0 - v_blur), 0.25, dist_sq);
+            alpha /= 1.0 + v_blur * 2.0;
+
+            // Glow using squared distance approximation
+            // exp(-sqrt(x) * 5) ≈ exp(-x * 2.5) for small x
+            float glow = exp(-dist_sq * 10.0) * 0.4 * (1.0 - v_blur);
+
+            color = vec4(rgb * (1.0 + glow), alpha * v_color.a);
         }
+#endif
 
-        // default (circle)
-        float dist_sq = dot(rotated, rotated);
-        
-        // thresholds: radius 0.25 and 0.5 squared = 0.0625, 0.25
-        float alpha = 1.0 - smoothstep(0.0625, 0.25, dist_sq);
+#ifdef SPARKLE
+        color.rgb = mix(color.rgb * v_sparkle.x, vec3(1.0), min(v_sparkle.y, 1.0));
+        color.a = min(color.a * (v_sparkle.x + v_sparkle.y), 1.0);
+#endif
 
-        // Glow using squared distance approximation
-        // exp(-sqrt(x) * 5) ≈ exp(-x * 2.5) for small x
-        float glow = exp(-dist_sq * 10.0) * 0.4;
+#ifdef STREAKS
+        // Streaks spread the same light over a longer path
+        color.a *= 1.0 - v_streak.z * 0.5;
+#endif
+        color = shade(color, gl_PointCoord);
+#ifdef FOG
+        color.rgb = mix(color.rgb, u_fogColor, v_fog);
+#endif
+#ifdef MASK
+        // The mask is rasterized top row first
+        vec2 maskCoord = gl_FragCoord.xy * u_maskScale;
+        color.a *= 1.0 - texture2D(u_mask, vec2(maskCoord.x, 1.0 - maskCoord.y)).a;
+#endif
+        gl_FragColor = color;
+    }
+"#;
 
-        gl_FragColor = vec4(u_colorTint * (1.0 + glow), alpha * v_color.a);
+/// Fullscreen quad shared by the post-processing passes.
+pub const POST_VERTEX_SHADER_SOURCE: &str = r#"
+    attribute vec2 a_quad;
+    varying vec2 v_uv;
+
+    void main() {
+        v_uv = a_quad * 0.5 + 0.5;
+        gl_Position = vec4(a_quad, 0.0, 1.0);
+    }
+"#;
+
+/// Keeps the part of each pixel above the bloom threshold.
+pub const BRIGHT_PASS_SHADER_SOURCE: &str = r#"
+    precision mediump float;
+
+    uniform sampler2D u_source;
+    uniform float u_threshold;
+    varying vec2 v_uv;
+
+    void main() {
+        vec4 color = texture2D(u_source, v_uv);
+        float brightness = max(color.r, max(color.g, color.b));
+        float excess = max(brightness - u_threshold, 0.0) / max(brightness, 0.0001);
+        gl_FragColor = color * excess;
+    }
+"#;
+
+/// One direction of a separable 9-tap Gaussian blur.
+pub const BLUR_SHADER_SOURCE: &str = r#"
+    precision mediump float;
+
+    uniform sampler2D u_source;
+    uniform vec2 u_step;       // texel offset along the blur direction
+    varying vec2 v_uv;
+
+    void main() {
+        vec4 sum = texture2D(u_source, v_uv) * 0.2270270270;
+        sum += (texture2D(u_source, v_uv + u_step * 1.3846153846)
+            + texture2D(u_source, v_uv - u_step * 1.3846153846)) * 0.3162162162;
+        sum += (texture2D(u_source, v_uv + u_step * 3.2307692308)
+            + texture2D(u_source, v_uv - u_step * 3.2307692308)) * 0.0702702703;
+        gl_FragColor = sum;
     }
 "#;
+
+/// Adds the blurred highlights to the scene and darkens the edges. Colors
+/// are premultiplied, as the canvas expects.
+pub const COMPOSITE_SHADER_SOURCE: &str = r#"
+    precision mediump float;
+
+    uniform sampler2D u_scene;
+    uniform sampler2D u_bloom;
+    uniform float u_bloomIntensity;
+    uniform float u_vignette;
+    uniform float u_aspect;
+    varying vec2 v_uv;
+
+    void main() {
+        vec4 color = texture2D(u_scene, v_uv);
+        vec3 bloom = texture2D(u_bloom, v_uv).rgb * u_bloomIntensity;
+        color.rgb += bloom;
+        color.a = min(max(color.a, max(bloom.r, max(bloom.g, bloom.b))), 1.0);
+
+        // Black layer over the edges, composited with "over"
+        vec2 d = (v_uv - 0.5) * vec2(u_aspect, 1.0);
+        float shade = u_vignette * smoothstep(0.3, 0.9, length(d));
+        color = color * (1.0 - shade) + vec4(0.0, 0.0, 0.0, shade);
+        gl_FragColor = color;
+    }
+"#;
+
+/// Vertical gradient with an optional image fitted over it. `u_imageScale`
+/// is the canvas size in units of the displayed image size.
+pub const BACKGROUND_SHADER_SOURCE: &str = r#"
+    precision mediump float;
+
+    uniform vec4 u_top;
+    uniform vec4 u_bottom;
+    uniform sampler2D u_image;
+    uniform float u_hasImage;
+    uniform vec2 u_imageScale;
+    varying vec2 v_uv;
+
+    void main() {
+        vec4 color = mix(u_bottom, u_top, v_uv.y);
+        color.rgb *= color.a;
+
+        if (u_hasImage > 0.5) {
+            vec2 uv = (v_uv - 0.5) * u_imageScale + 0.5;
+            if (uv == clamp(uv, 0.0, 1.0)) {
+                // Images are uploaded top row first
+                vec4 image = texture2D(u_image, vec2(uv.x, 1.0 - uv.y));
+                color = vec4(image.rgb * image.a, image.a) + color * (1.0 - image.a);
+            }
+        }
+        gl_FragColor = color;
+    }
+"#;
+
+/// Horizontal plane at `u_groundY` centered under the camera. Depth is
+/// clamped so the plane reaches the horizon past the far plane.
+pub const GROUND_VERTEX_SHADER_SOURCE: &str = r#"
+    precision highp float;
+
+    attribute vec2 a_quad;
+    uniform mat4 u_projection;
+    uniform mat4 u_view;
+    uniform float u_groundY;
+    uniform vec3 u_plane;            // center x, center z, half size
+    varying vec2 v_ground;
+    varying float v_depth;
+
+    void main() {
+        vec3 pos = vec3(u_plane.x, u_groundY, u_plane.y) + vec3(a_quad.x, 0.0, a_quad.y) * u_plane.z;
+        vec4 viewPos = u_view * vec4(pos, 1.0);
+        v_ground = pos.xz;
+        v_depth = -viewPos.z;
+        gl_Position = u_projection * viewPos;
+        gl_Position.z = min(gl_Position.z, gl_Position.w * 0.9999);
+    }
+"#;
+
+/// Patchy snow cover growing over the ground color, fogged like the flakes.
+pub const GROUND_SHADER_SOURCE: &str = r#"
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+    precision highp float;
+#else
+    precision mediump float;
+#endif
+
+    uniform vec3 u_groundColor;
+    uniform vec3 u_snowColor;
+    uniform float u_cover;           // 0 bare, 1 fully white
+    uniform float u_detailDistance;  // depth where the patches blur out
+    uniform int u_fogMode;
+    uniform vec3 u_fogParams;
+    uniform vec3 u_fogColor;
+    varying vec2 v_ground;
+    varying float v_depth;
+
+    float hash(vec2 p) {
+        return fract(sin(dot(p, vec2(127.1, 311.7))) * 43758.5453);
+    }
+
+    float noise(vec2 p) {
+        vec2 i = floor(p);
+        vec2 f = fract(p);
+        f = f * f * (3.0 - 2.0 * f);
+        return mix(mix(hash(i), hash(i + vec2(1.0, 0.0)), f.x),
+                   mix(hash(i + vec2(0.0, 1.0)), hash(i + vec2(1.0, 1.0)), f.x), f.y);
+    }
+
+    void main() {
+        vec2 p = v_ground * 0.08;
+        float n = noise(p) * 0.6 + noise(p * 3.1) * 0.3 + noise(p * 9.7) * 0.1;
+        // Distant patches alias, so they fade to the average
+        n = mix(n, 0.5, clamp(v_depth / u_detailDistance, 0.0, 1.0));
+        float snow = smoothstep(n, n + 0.1, u_cover * 1.1);
+        vec3 color = mix(u_groundColor, u_snowColor, snow);
+
+        float fog = 0.0;
+        float d = u_fogParams.z * max(v_depth - u_fogParams.x, 0.0);
+        if (u_fogMode == 1) {
+            fog = clamp((v_depth - u_fogParams.x) / (u_fogParams.y - u_fogParams.x), 0.0, 1.0);
+        } else if (u_fogMode == 2) {
+            fog = 1.0 - exp(-d);
+        } else if (u_fogMode == 3) {
+            fog = 1.0 - exp(-d * d);
+        }
+        gl_FragColor = vec4(mix(color, u_fogColor, fog), 1.0);
+    }
+"#;
+
+/// Glass over the snow globe: brighter rim, a soft reflection and a
+/// vignette outside. `u_globeCircle` is the outline in NDC, radius in NDC
+/// height units.
+pub const GLASS_SHADER_SOURCE: &str = r#"
+    precision mediump float;
+
+    uniform vec3 u_globeCircle;
+    uniform float u_aspect;
+    uniform float u_glass;           // 0-1 strength
+    varying vec2 v_uv;
+
+    void main() {
+        vec2 d = (v_uv * 2.0 - 1.0 - u_globeCircle.xy) * vec2(u_aspect, 1.0) / u_globeCircle.z;
+        float r = length(d);
+        float inside = 1.0 - smoothstep(0.99, 1.01, r);
+
+        // Glass looks thicker at grazing angles
+        float rim = smoothstep(0.75, 1.0, r) * inside;
+        vec2 h = d - vec2(-0.45, 0.5);
+        float reflection = exp(-dot(h, h) * 18.0) * inside;
+        float outside = smoothstep(1.0, 1.8, r) * (1.0 - inside) * 0.7;
+
+        // White light inside, black shade outside
+        float light = rim * 0.25 + reflection * 0.5;
+        float alpha = (light + outside) * u_glass;
+        gl_FragColor = vec4(vec3(light / max(light + outside, 0.001)), alpha);
+    }
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defines(features: ShaderFeatures) -> Vec<String> {
+        features
+            .source("void main() {}")
+            .lines()
+            .filter_map(|line| line.strip_prefix("#define "))
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn set_and_contains() {
+        let mut features = ShaderFeatures::default();
+        assert!(!features.contains(ShaderFeatures::FOG));
+        features.set(ShaderFeatures::FOG, true);
+        features.set(ShaderFeatures::EMITTER, true);
+        assert!(features.contains(ShaderFeatures::FOG));
+        assert!(features.contains(ShaderFeatures::EMITTER));
+        assert!(!features.contains(ShaderFeatures::GLOBE));
+        features.set(ShaderFeatures::FOG, false);
+        assert!(!features.contains(ShaderFeatures::FOG));
+        assert!(features.contains(ShaderFeatures::EMITTER));
+    }
+
+    #[test]
+    fn source_defines_only_enabled_features() {
+        assert!(defines(ShaderFeatures::default()).is_empty());
+        assert_eq!(
+            ShaderFeatures::default().source("void main() {}"),
+            "void main() {}"
+        );
+
+        let mut features = ShaderFeatures::default();
+        features.set(ShaderFeatures::TEXTURE, true);
+        features.set(ShaderFeatures::GROUND, true);
+        features.set(ShaderFeatures::EMITTER, true);
+        assert_eq!(defines(features), ["TEXTURE", "GROUND", "EMITTER"]);
+        assert!(features
+            .source("void main() {}")
+            .ends_with("\nvoid main() {}"));
+    }
+
+    #[test]
+    fn every_feature_has_its_own_define() {
+        for (feature, name) in ShaderFeatures::DEFINES {
+            let mut features = ShaderFeatures::default();
+            features.set(feature, true);
+            assert_eq!(defines(features), [name]);
+        }
+    }
+}
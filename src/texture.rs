@@ -0,0 +1,544 @@
+//! Texture sources and atlases combining several flake shapes
+
+use crate::config::{get, get_bool, get_f32};
+use crate::constants::*;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::Clamped;
+use web_sys::WebGlRenderingContext as GL;
+use web_sys::{
+    CanvasRenderingContext2d, HtmlCanvasElement, HtmlImageElement, ImageBitmap, ImageData,
+    OffscreenCanvas,
+};
+
+/// Anything a texture can be uploaded from.
+#[derive(Clone, Debug)]
+pub enum TextureSource {
+    Image(HtmlImageElement),
+    Bitmap(ImageBitmap),
+    Canvas(HtmlCanvasElement),
+    Offscreen(OffscreenCanvas),
+    Data(ImageData),
+    /// Tightly packed RGBA bytes, row by row from the top.
+    Raw {
+        data: Vec<u8>,
+        width: u32,
+        height: u32,
+    },
+}
+
+impl TextureSource {
+    /// Accepts an image, `ImageBitmap`, canvas, `OffscreenCanvas`, `ImageData`
+    /// or `{ data, width, height }` with `data` a `Uint8Array` or
+    /// `Uint8ClampedArray` of RGBA bytes.
+    pub fn from_js(value: &JsValue) -> Option<Self> {
+        if let Some(image) = value.dyn_ref::<HtmlImageElement>() {
+            return Some(Self::Image(image.clone()));
+        }
+        if let Some(bitmap) = value.dyn_ref::<ImageBitmap>() {
+            return Some(Self::Bitmap(bitmap.clone()));
+        }
+        if let Some(canvas) = value.dyn_ref::<HtmlCanvasElement>() {
+            return Some(Self::Canvas(canvas.clone()));
+        }
+        if let Some(canvas) = value.dyn_ref::<OffscreenCanvas>() {
+            return Some(Self::Offscreen(canvas.clone()));
+        }
+        if let Some(data) = value.dyn_ref::<ImageData>() {
+            return Some(Self::Data(data.clone()));
+        }
+
+        let data = get(value, "data")?;
+        let data = if let Some(bytes) = data.dyn_ref::<js_sys::Uint8Array>() {
+            bytes.to_vec()
+        } else {
+            data.dyn_ref::<js_sys::Uint8ClampedArray>()?.to_vec()
+        };
+        let width = get_f32(value, "width")? as u32;
+        let height = get_f32(value, "height")? as u32;
+        if data.len() < raw_len(width, height)? {
+            return None;
+        }
+        Some(Self::Raw {
+            data,
+            width,
+            height,
+        })
+    }
+
+    /// `false` while an image is still loading.
+    pub fn is_ready(&self) -> bool {
+        match self {
+            Self::Image(image) => image.complete(),
+            _ => true,
+        }
+    }
+
+    /// Intrinsic size; 0×0 for SVG images without width and height.
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            Self::Image(image) => (image.natural_width(), image.natural_height()),
+            Self::Bitmap(bitmap) => (bitmap.width(), bitmap.height()),
+            Self::Canvas(canvas) => (canvas.width(), canvas.height()),
+            Self::Offscreen(canvas) => (canvas.width(), canvas.height()),
+            Self::Data(data) => (data.width(), data.height()),
+            Self::Raw { width, height, .. } => (*width, *height),
+        }
+    }
+
+    /// Uploads into level 0 of the texture bound to `TEXTURE_2D`.
+    pub fn upload(&self, gl: &GL) -> Result<(), JsValue> {
+        let (target, format, kind) = (GL::TEXTURE_2D, GL::RGBA, GL::UNSIGNED_BYTE);
+        match self {
+            Self::Image(image) => gl.tex_image_2d_with_u32_and_u32_and_image(
+                target,
+                0,
+                format as i32,
+                format,
+                kind,
+                image,
+            ),
+            Self::Bitmap(bitmap) => gl.tex_image_2d_with_u32_and_u32_and_image_bitmap(
+                target,
+                0,
+                format as i32,
+                format,
+                kind,
+                bitmap,
+            ),
+            Self::Canvas(canvas) => gl.tex_image_2d_with_u32_and_u32_and_canvas(
+                target,
+                0,
+                format as i32,
+                format,
+                kind,
+                canvas,
+            ),
+            // web-sys has no WebGL1 overload for offscreen canvases, so they
+            // go through a copy in an HTML canvas
+            Self::Offscreen(canvas) => self.resized(canvas.width(), canvas.height())?.upload(gl),
+            Self::Data(data) => gl.tex_image_2d_with_u32_and_u32_and_image_data(
+                target,
+                0,
+                format as i32,
+                format,
+                kind,
+                data,
+            ),
+            Self::Raw {
+                data,
+                width,
+                height,
+            } => gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                target,
+                0,
+                format as i32,
+                *width as i32,
+                *height as i32,
+                0,
+                format,
+                kind,
+                Some(data),
+            ),
+        }
+    }
+
+    /// Draws the source scaled into the given rectangle.
+    pub fn draw(
+        &self,
+        ctx: &CanvasRenderingContext2d,
+        x: f64,
+        y: f64,
+        w: f64,
+        h: f64,
+    ) -> Result<(), JsValue> {
+        match self {
+            Self::Image(image) => {
+                ctx.draw_image_with_html_image_element_and_dw_and_dh(image, x, y, w, h)
+            }
+            Self::Bitmap(bitmap) => {
+                ctx.draw_image_with_image_bitmap_and_dw_and_dh(bitmap, x, y, w, h)
+            }
+            Self::Canvas(canvas) => {
+                ctx.draw_image_with_html_canvas_element_and_dw_and_dh(canvas, x, y, w, h)
+            }
+            Self::Offscreen(canvas) => {
+                ctx.draw_image_with_offscreen_canvas_and_dw_and_dh(canvas, x, y, w, h)
+            }
+            Self::Data(data) => draw_image_data(ctx, data, x, y, w, h),
+            Self::Raw {
+                data,
+                width,
+                height,
+            } => {
+                let len = raw_len(*width, *height).ok_or("Invalid raw texture size")?;
+                let data = ImageData::new_with_u8_clamped_array_and_sh(
+                    Clamped(&data[..len]),
+                    *width,
+                    *height,
+                )?;
+                draw_image_data(ctx, &data, x, y, w, h)
+            }
+        }
+    }
+
+    /// Redraws the source into a canvas of the given size.
+    pub fn resized(&self, width: u32, height: u32) -> Result<Self, JsValue> {
+        let (canvas, ctx) = create_canvas(width, height)?;
+        self.draw(&ctx, 0.0, 0.0, width as f64, height as f64)?;
+        Ok(Self::Canvas(canvas))
+    }
+}
+
+/// Byte length of `width`×`height` RGBA pixels, `None` for empty sizes or
+/// sides over `TEXTURE_MAX_SIZE`.
+fn raw_len(width: u32, height: u32) -> Option<usize> {
+    if width == 0 || height == 0 || width.max(height) > TEXTURE_MAX_SIZE {
+        return None;
+    }
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|n| n.checked_mul(4))
+}
+
+/// Animation frames laid out in a grid, played row by row.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteSheet {
+    /// Frames per row.
+    pub frames: u32,
+    pub rows: u32,
+    pub fps: f32,
+}
+
+impl SpriteSheet {
+    pub fn new(frames: u32, rows: u32, fps: f32) -> Self {
+        Self {
+            frames: frames.max(1),
+            rows: rows.max(1),
+            fps: fps.max(0.0),
+        }
+    }
+
+    /// Reads `{ frames, rows, fps }`; `frames` is required.
+    pub fn from_js(value: &JsValue) -> Option<Self> {
+        let frames = get_f32(value, "frames")? as u32;
+        let rows = get_f32(value, "rows").unwrap_or(1.0) as u32;
+        let fps = get_f32(value, "fps").unwrap_or(DEFAULT_SPRITE_FPS);
+        Some(Self::new(frames, rows, fps))
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.frames * self.rows
+    }
+
+    /// Frame shown at `time` seconds, before the per-particle phase offset.
+    pub fn frame_at(&self, time: f32) -> f32 {
+        (time * self.fps).rem_euclid(self.frame_count() as f32)
+    }
+}
+
+/// How textures are uploaded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureOptions {
+    /// Generates mipmaps, resizing to power-of-two dimensions first.
+    pub mipmaps: bool,
+    pub premultiply_alpha: bool,
+    pub flip_y: bool,
+    /// Longest side the source is rasterized at. Required for SVG images
+    /// without intrinsic size, which otherwise use `DEFAULT_TEXTURE_SIZE`.
+    pub size: Option<u32>,
+    /// Anisotropic filtering level, 1 disables it.
+    pub anisotropy: f32,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            mipmaps: true,
+            premultiply_alpha: false,
+            flip_y: false,
+            size: None,
+            anisotropy: DEFAULT_ANISOTROPY,
+        }
+    }
+}
+
+impl TextureOptions {
+    /// Reads `{ mipmaps, premultiplyAlpha, flipY, size, anisotropy }`.
+    pub fn from_js(value: &JsValue) -> Self {
+        let mut options = Self::default();
+        if let Some(v) = get_bool(value, "mipmaps") {
+            options.mipmaps = v;
+        }
+        if let Some(v) = get_bool(value, "premultiplyAlpha") {
+            options.premultiply_alpha = v;
+        }
+        if let Some(v) = get_bool(value, "flipY") {
+            options.flip_y = v;
+        }
+        if let Some(v) = get_f32(value, "size") {
+            options.size = Some((v as u32).clamp(1, TEXTURE_MAX_SIZE));
+        }
+        if let Some(v) = get_f32(value, "anisotropy") {
+            options.anisotropy = v.max(1.0);
+        }
+        options
+    }
+
+    /// Size a `width`×`height` source is uploaded at: scaled to `size`,
+    /// rounded up to powers of two for mipmapping and capped at
+    /// `TEXTURE_MAX_SIZE`.
+    pub fn upload_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let (width, height) = if width == 0 || height == 0 {
+            let side = self.size.unwrap_or(DEFAULT_TEXTURE_SIZE);
+            (side, side)
+        } else if let Some(side) = self.size {
+            let scale = side as f32 / width.max(height) as f32;
+            (
+                ((width as f32 * scale).round() as u32).max(1),
+                ((height as f32 * scale).round() as u32).max(1),
+            )
+        } else {
+            (width, height)
+        };
+
+        let fit = |n: u32| {
+            let n = if self.mipmaps {
+                n.next_power_of_two()
+            } else {
+                n
+            };
+            n.min(TEXTURE_MAX_SIZE)
+        };
+        (fit(width), fit(height))
+    }
+}
+
+/// `putImageData` can't scale, so the pixels go through a scratch canvas.
+fn draw_image_data(
+    ctx: &CanvasRenderingContext2d,
+    data: &ImageData,
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+) -> Result<(), JsValue> {
+    let (canvas, scratch) = create_canvas(data.width(), data.height())?;
+    scratch.put_image_data(data, 0.0, 0.0)?;
+    ctx.draw_image_with_html_canvas_element_and_dw_and_dh(&canvas, x, y, w, h)
+}
+
+/// One shape of an atlas and how often particles use it.
+#[derive(Clone, Debug)]
+pub struct AtlasEntry {
+    pub source: TextureSource,
+    pub weight: f32,
+}
+
+impl AtlasEntry {
+    /// Accepts any `TextureSource` or `{ image, weight }`.
+    pub fn from_js(value: JsValue) -> Option<Self> {
+        if let Some(source) = TextureSource::from_js(&value) {
+            return Some(Self {
+                source,
+                weight: 1.0,
+            });
+        }
+        let source = TextureSource::from_js(&get(&value, "image")?)?;
+        let weight = get_f32(&value, "weight").unwrap_or(1.0);
+        Some(Self { source, weight })
+    }
+
+    /// Parses an array of entries, skipping invalid ones.
+    pub fn parse_list(value: &JsValue) -> Vec<Self> {
+        if !js_sys::Array::is_array(value) {
+            return Vec::new();
+        }
+        js_sys::Array::from(value)
+            .iter()
+            .filter_map(Self::from_js)
+            .collect()
+    }
+}
+
+/// Grid of square cells, filled row by row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasLayout {
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl AtlasLayout {
+    /// Smallest near-square grid holding `count` cells.
+    pub fn for_count(count: usize) -> Self {
+        let count = count.max(1) as u32;
+        let columns = (count as f32).sqrt().ceil() as u32;
+        let rows = count.div_ceil(columns);
+        Self { columns, rows }
+    }
+
+    /// Top-left pixel of a cell.
+    pub fn cell_origin(&self, index: u32, cell_size: u32) -> (u32, u32) {
+        (
+            (index % self.columns) * cell_size,
+            (index / self.columns) * cell_size,
+        )
+    }
+}
+
+/// Draws the sources into a canvas, one per cell. The cell size follows the
+/// largest source, clamped to `ATLAS_MIN_CELL_SIZE..=ATLAS_MAX_CELL_SIZE`.
+pub fn compose_atlas(entries: &[AtlasEntry]) -> Result<(HtmlCanvasElement, AtlasLayout), JsValue> {
+    let layout = AtlasLayout::for_count(entries.len());
+    let cell = entries
+        .iter()
+        .map(|e| {
+            let (w, h) = e.source.size();
+            w.max(h)
+        })
+        .max()
+        .unwrap_or(0)
+        .clamp(ATLAS_MIN_CELL_SIZE, ATLAS_MAX_CELL_SIZE);
+
+    let (canvas, ctx) = create_canvas(layout.columns * cell, layout.rows * cell)?;
+
+    // Padding keeps linear filtering from bleeding between cells
+    let pad = ATLAS_CELL_PADDING as f64;
+    let size = cell as f64 - pad * 2.0;
+    for (i, entry) in entries.iter().enumerate() {
+        let (x, y) = layout.cell_origin(i as u32, cell);
+        entry
+            .source
+            .draw(&ctx, x as f64 + pad, y as f64 + pad, size, size)?;
+    }
+
+    Ok((canvas, layout))
+}
+
+pub(crate) fn create_canvas(
+    width: u32,
+    height: u32,
+) -> Result<(HtmlCanvasElement, CanvasRenderingContext2d), JsValue> {
+    let canvas: HtmlCanvasElement = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or("Failed to get document")?
+        .create_element("canvas")?
+        .dyn_into()?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+
+    let ctx = canvas
+        .get_context("2d")?
+        .ok_or("Canvas 2D is not supported")?
+        .dyn_into()?;
+    Ok((canvas, ctx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_len_counts_rgba_bytes() {
+        assert_eq!(raw_len(1, 1), Some(4));
+        assert_eq!(raw_len(3, 2), Some(24));
+        assert_eq!(
+            raw_len(TEXTURE_MAX_SIZE, TEXTURE_MAX_SIZE),
+            Some((TEXTURE_MAX_SIZE * TEXTURE_MAX_SIZE * 4) as usize)
+        );
+    }
+
+    #[test]
+    fn raw_len_rejects_empty_and_oversized() {
+        assert_eq!(raw_len(0, 16), None);
+        assert_eq!(raw_len(16, 0), None);
+        assert_eq!(raw_len(TEXTURE_MAX_SIZE + 1, 1), None);
+        assert_eq!(raw_len(1, TEXTURE_MAX_SIZE + 1), None);
+        // Would wrap around in 32-bit arithmetic
+        assert_eq!(raw_len(65536, 65536), None);
+        assert_eq!(raw_len(u32::MAX, u32::MAX), None);
+    }
+
+    fn options(mipmaps: bool, size: Option<u32>) -> TextureOptions {
+        TextureOptions {
+            mipmaps,
+            size,
+            ..TextureOptions::default()
+        }
+    }
+
+    #[test]
+    fn upload_size_falls_back_for_sizeless_svg() {
+        let side = DEFAULT_TEXTURE_SIZE;
+        assert_eq!(options(false, None).upload_size(0, 0), (side, side));
+        assert_eq!(options(false, None).upload_size(0, 100), (side, side));
+        assert_eq!(options(false, Some(100)).upload_size(0, 0), (100, 100));
+        assert_eq!(options(true, Some(100)).upload_size(0, 0), (128, 128));
+    }
+
+    #[test]
+    fn upload_size_scales_longest_side_to_size() {
+        let options = options(false, Some(100));
+        assert_eq!(options.upload_size(400, 200), (100, 50));
+        assert_eq!(options.upload_size(30, 60), (50, 100));
+        assert_eq!(options.upload_size(1000, 1), (100, 1));
+    }
+
+    #[test]
+    fn upload_size_rounds_up_to_powers_of_two_for_mipmaps() {
+        assert_eq!(options(true, None).upload_size(100, 64), (128, 64));
+        assert_eq!(options(true, None).upload_size(1, 129), (1, 256));
+        assert_eq!(options(false, None).upload_size(100, 64), (100, 64));
+        assert_eq!(options(true, Some(300)).upload_size(600, 200), (512, 128));
+    }
+
+    #[test]
+    fn upload_size_is_capped() {
+        let max = TEXTURE_MAX_SIZE;
+        assert_eq!(options(true, None).upload_size(max + 1, 10), (max, 16));
+        assert_eq!(options(false, None).upload_size(10, max * 3), (10, max));
+        assert_eq!(options(false, None).upload_size(max, max), (max, max));
+    }
+
+    #[test]
+    fn sprite_sheet_wraps_frames() {
+        let sheet = SpriteSheet::new(4, 2, 8.0);
+        assert_eq!(sheet.frame_count(), 8);
+        assert_eq!(sheet.frame_at(0.0), 0.0);
+        assert_eq!(sheet.frame_at(0.5), 4.0);
+        assert_eq!(sheet.frame_at(1.0), 0.0);
+        assert_eq!(sheet.frame_at(1.25), 2.0);
+        // Negative times wrap backwards instead of going negative
+        assert_eq!(sheet.frame_at(-0.125), 7.0);
+        assert_eq!(sheet.frame_at(-2.0), 0.0);
+    }
+
+    #[test]
+    fn sprite_sheet_without_fps_stays_on_first_frame() {
+        let sheet = SpriteSheet::new(4, 1, 0.0);
+        assert_eq!(sheet.frame_at(0.0), 0.0);
+        assert_eq!(sheet.frame_at(123.0), 0.0);
+        assert_eq!(SpriteSheet::new(4, 1, -5.0).fps, 0.0);
+    }
+
+    #[test]
+    fn single_frame_sprite_sheet() {
+        let sheet = SpriteSheet::new(0, 0, 12.0);
+        assert_eq!((sheet.frames, sheet.rows), (1, 1));
+        assert_eq!(sheet.frame_count(), 1);
+        for time in [0.0, 0.5, 10.0, -3.0] {
+            assert!((0.0..1.0).contains(&sheet.frame_at(time)));
+        }
+    }
+
+    #[test]
+    fn atlas_layout_is_near_square() {
+        for (count, columns, rows) in [(0, 1, 1), (1, 1, 1), (2, 2, 1), (5, 3, 2), (9, 3, 3)] {
+            let layout = AtlasLayout::for_count(count);
+            assert_eq!((layout.columns, layout.rows), (columns, rows), "{count}");
+        }
+        let layout = AtlasLayout::for_count(5);
+        assert_eq!(layout.cell_origin(0, 10), (0, 0));
+        assert_eq!(layout.cell_origin(2, 10), (20, 0));
+        assert_eq!(layout.cell_origin(4, 10), (10, 10));
+    }
+}
@@ -0,0 +1,206 @@
+//! Particle themes beyond snow
+
+use wasm_bindgen::prelude::*;
+
+/// Sprite shape drawn when no texture is set.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    Circle = 0,
+    Rectangle = 1,
+    Petal = 2,
+    Leaf = 3,
+    Ember = 4,
+}
+
+impl Shape {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "circle" => Some(Self::Circle),
+            "rectangle" => Some(Self::Rectangle),
+            "petal" => Some(Self::Petal),
+            "leaf" => Some(Self::Leaf),
+            "ember" => Some(Self::Ember),
+            _ => None,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    /// Adds light, for glowing particles.
+    Additive,
+}
+
+impl BlendMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "normal" => Some(Self::Normal),
+            "additive" => Some(Self::Additive),
+            _ => None,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Theme {
+    Snow,
+    Confetti,
+    Petals,
+    Leaves,
+    /// Glowing sparks rising upwards.
+    Embers,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 5] = [
+        Self::Snow,
+        Self::Confetti,
+        Self::Petals,
+        Self::Leaves,
+        Self::Embers,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Snow => "snow",
+            Self::Confetti => "confetti",
+            Self::Petals => "petals",
+            Self::Leaves => "leaves",
+            Self::Embers => "embers",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    pub fn preset(self) -> ThemePreset {
+        match self {
+            Self::Snow => ThemePreset::SNOW,
+            Self::Confetti => ThemePreset::CONFETTI,
+            Self::Petals => ThemePreset::PETALS,
+            Self::Leaves => ThemePreset::LEAVES,
+            Self::Embers => ThemePreset::EMBERS,
+        }
+    }
+}
+
+/// Motion fields a theme overrides in `SnowConfig`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThemeMotion {
+    pub gravity: f32,
+    pub speed_y: f32,
+    pub direction_y: f32,
+    pub min_size: f32,
+    pub min_alpha: f32,
+    pub rotation_speed: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThemePreset {
+    pub shape: Shape,
+    /// Speed of flipping around the sprite's X and Y axes, 0 keeps flakes flat.
+    pub tumble: f32,
+    pub blend: BlendMode,
+    /// Colors picked at random per particle.
+    pub palette: &'static [[f32; 3]],
+    /// `None` keeps the motion of the current precipitation.
+    pub motion: Option<ThemeMotion>,
+}
+
+impl ThemePreset {
+    pub const SNOW: Self = Self {
+        shape: Shape::Circle,
+        tumble: 0.0,
+        blend: BlendMode::Normal,
+        palette: &[[1.0, 1.0, 1.0]],
+        motion: None,
+    };
+
+    pub const CONFETTI: Self = Self {
+        shape: Shape::Rectangle,
+        tumble: 3.0,
+        blend: BlendMode::Normal,
+        palette: &[
+            [0.96, 0.26, 0.21],
+            [1.0, 0.76, 0.03],
+            [0.3, 0.69, 0.31],
+            [0.13, 0.59, 0.95],
+            [0.61, 0.15, 0.69],
+            [1.0, 0.34, 0.13],
+        ],
+        motion: Some(ThemeMotion {
+            gravity: 4.0,
+            speed_y: 0.5,
+            direction_y: 1.0,
+            min_size: 4.0,
+            min_alpha: 0.9,
+            rotation_speed: 2.5,
+        }),
+    };
+
+    pub const PETALS: Self = Self {
+        shape: Shape::Petal,
+        tumble: 1.2,
+        blend: BlendMode::Normal,
+        palette: &[
+            [1.0, 0.75, 0.8],
+            [0.98, 0.63, 0.72],
+            [1.0, 0.87, 0.9],
+            [0.95, 0.55, 0.66],
+        ],
+        motion: Some(ThemeMotion {
+            gravity: 2.5,
+            speed_y: 0.4,
+            direction_y: 1.0,
+            min_size: 5.0,
+            min_alpha: 0.8,
+            rotation_speed: 1.0,
+        }),
+    };
+
+    pub const LEAVES: Self = Self {
+        shape: Shape::Leaf,
+        tumble: 1.5,
+        blend: BlendMode::Normal,
+        palette: &[
+            [0.85, 0.33, 0.1],
+            [0.93, 0.6, 0.13],
+            [0.72, 0.2, 0.09],
+            [0.6, 0.45, 0.15],
+            [0.95, 0.75, 0.25],
+        ],
+        motion: Some(ThemeMotion {
+            gravity: 3.0,
+            speed_y: 0.5,
+            direction_y: 1.0,
+            min_size: 6.0,
+            min_alpha: 0.85,
+            rotation_speed: 1.2,
+        }),
+    };
+
+    pub const EMBERS: Self = Self {
+        shape: Shape::Ember,
+        tumble: 0.0,
+        blend: BlendMode::Additive,
+        palette: &[
+            [1.0, 0.45, 0.1],
+            [1.0, 0.6, 0.15],
+            [1.0, 0.3, 0.05],
+            [1.0, 0.8, 0.35],
+        ],
+        motion: Some(ThemeMotion {
+            gravity: 3.0,
+            speed_y: 0.4,
+            direction_y: -1.0,
+            min_size: 1.5,
+            min_alpha: 0.4,
+            rotation_speed: 0.5,
+        }),
+    };
+}